@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_tree_walk::{Backend, Dialect, Limits};
+
+fuzz_target!(|data: &[u8]| {
+    let code = String::from_utf8_lossy(data);
+    let mut limits = Limits::new();
+    limits.set_max_steps(Some(10_000));
+    limits.set_max_memory(Some(1 << 20));
+
+    for backend in [Backend::TreeWalk, Backend::Bytecode] {
+        let mut executor = backend.executor(Dialect::Extended);
+        executor.set_limits(limits);
+        let _ = executor.run(&code);
+    }
+});
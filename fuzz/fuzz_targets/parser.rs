@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_tree_walk::{Dialect, Scanner, parser::Parser};
+
+fuzz_target!(|data: &[u8]| {
+    let code = String::from_utf8_lossy(data);
+
+    for dialect in [Dialect::Extended, Dialect::Reference] {
+        let scanner = Scanner::new(&code, dialect);
+        let mut tokens = scanner.tokens();
+        let _ = Parser::with_dialect(&code, &mut tokens, dialect).parse();
+    }
+});
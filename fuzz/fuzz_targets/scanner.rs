@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_tree_walk::{Dialect, Scanner};
+
+fuzz_target!(|data: &[u8]| {
+    let code = String::from_utf8_lossy(data);
+
+    for dialect in [Dialect::Extended, Dialect::Reference] {
+        let scanner = Scanner::new(&code, dialect);
+
+        for token in scanner.tokens() {
+            if let Ok(token) = token {
+                let _ = token.lexeme(&code);
+            }
+        }
+    }
+});
@@ -1,35 +1,62 @@
-use std::fmt;
+use std::rc::Rc;
 
 use super::{
-    token::Token,
+    token::{Token, Span},
     value::Value
 };
 
-pub trait Expression: fmt::Debug {
-    fn accept(&self, visitor: &mut dyn ExpressionVisitor);
-
-    fn as_variable(&self) -> Option<&Variable> {
-        None
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Binary(Binary),
+    Grouping(Grouping),
+    Literal(Literal),
+    Unary(Unary),
+    Ternary(Ternary),
+    Variable(Variable),
+    Assign(Assign),
+    Logical(Logical),
+    Interpolation(Interpolation),
+    MethodCall(MethodCall),
+    Call(Call)
+}
+
+impl Expression {
+    pub fn accept<T>(&self, visitor: &mut dyn ExpressionVisitor<T>) -> T {
+        use Expression::*;
+
+        match self {
+            Binary(expression) => visitor.visit_binary(expression),
+            Grouping(expression) => visitor.visit_grouping(expression),
+            Literal(expression) => visitor.visit_literal(expression),
+            Unary(expression) => visitor.visit_unary(expression),
+            Ternary(expression) => visitor.visit_ternary(expression),
+            Variable(expression) => visitor.visit_variable(expression),
+            Assign(expression) => visitor.visit_assign(expression),
+            Logical(expression) => visitor.visit_logical(expression),
+            Interpolation(expression) => visitor.visit_interpolation(expression),
+            MethodCall(expression) => visitor.visit_method_call(expression),
+            Call(expression) => visitor.visit_call(expression)
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Binary {
-    left: Box<dyn Expression>,
+    left: Box<Expression>,
     operator: Token,
-    right: Box<dyn Expression>
+    right: Box<Expression>
 }
 
 impl Binary {
-    pub fn new(left: Box<dyn Expression>, operator: Token, right: Box<dyn Expression>) -> Self {
+    pub fn new(left: Expression, operator: Token, right: Expression) -> Self {
         Self {
-            left,
+            left: Box::new(left),
             operator,
-            right
+            right: Box::new(right)
         }
     }
 
-    pub fn left(&self) -> &dyn Expression {
+    pub fn left(&self) -> &Expression {
         self.left.as_ref()
     }
 
@@ -37,74 +64,77 @@ impl Binary {
         &self.operator
     }
 
-    pub fn right(&self) -> &dyn Expression {
+    pub fn right(&self) -> &Expression {
         self.right.as_ref()
     }
 }
 
-impl Expression for Binary {
-    fn accept(&self, visitor: &mut dyn ExpressionVisitor) {
-        visitor.visit_binary(self);
+/// Operators compare by type, so that the same code compares equal
+/// wherever it is.
+impl PartialEq for Binary {
+    fn eq(&self, other: &Self) -> bool {
+        self.operator.token_type() == other.operator.token_type() && self.left == other.left && self.right == other.right
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Grouping {
-    expression: Box<dyn Expression>
+    expression: Box<Expression>
 }
 
 impl Grouping {
-    pub fn new(expression: Box<dyn Expression>) -> Self {
+    pub fn new(expression: Expression) -> Self {
         Self {
-            expression
+            expression: Box::new(expression)
         }
     }
 
-    pub fn expression(&self) -> &dyn Expression {
+    pub fn expression(&self) -> &Expression {
         self.expression.as_ref()
     }
 }
 
-impl Expression for Grouping {
-    fn accept(&self, visitor: &mut dyn ExpressionVisitor) {
-        visitor.visit_grouping(self);
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Literal {
-    value: Value
+    value: Value,
+    line: usize
 }
 
 impl Literal {
-    pub fn new(value: Value) -> Self {
+    pub fn new(value: Value, line: usize) -> Self {
         Self {
-            value
+            value,
+            line
         }
     }
 
     pub fn value(&self) -> &Value {
         &self.value
     }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
 }
 
-impl Expression for Literal {
-    fn accept(&self, visitor: &mut dyn ExpressionVisitor) {
-        visitor.visit_literal(self);
+/// Lines are left out, like all positions in the tree.
+impl PartialEq for Literal {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Unary {
     operator: Token,
-    right: Box<dyn Expression>
+    right: Box<Expression>
 }
 
 impl Unary {
-    pub fn new(operator: Token, right: Box<dyn Expression>) -> Self {
+    pub fn new(operator: Token, right: Expression) -> Self {
         Self {
             operator,
-            right
+            right: Box::new(right)
         }
     }
 
@@ -112,37 +142,37 @@ impl Unary {
         &self.operator
     }
 
-    pub fn right(&self) -> &dyn Expression {
+    pub fn right(&self) -> &Expression {
         self.right.as_ref()
     }
 }
 
-impl Expression for Unary {
-    fn accept(&self, visitor: &mut dyn ExpressionVisitor) {
-        visitor.visit_unary(self);
+impl PartialEq for Unary {
+    fn eq(&self, other: &Self) -> bool {
+        self.operator.token_type() == other.operator.token_type() && self.right == other.right
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Ternary {
     operator: Token,
-    first: Box<dyn Expression>,
-    second: Box<dyn Expression>,
-    third: Box<dyn Expression>
+    first: Box<Expression>,
+    second: Box<Expression>,
+    third: Box<Expression>
 }
 
 impl Ternary {
     pub fn new(
         operator: Token,
-        first: Box<dyn Expression>,
-        second: Box<dyn Expression>,
-        third: Box<dyn Expression>
+        first: Expression,
+        second: Expression,
+        third: Expression
     ) -> Self {
         Self {
             operator,
-            first,
-            second,
-            third
+            first: Box::new(first),
+            second: Box::new(second),
+            third: Box::new(third)
         }
     }
 
@@ -150,98 +180,137 @@ impl Ternary {
         &self.operator
     }
 
-    pub fn first(&self) -> &dyn Expression {
+    pub fn first(&self) -> &Expression {
         self.first.as_ref()
     }
 
-    pub fn second(&self) -> &dyn Expression {
+    pub fn second(&self) -> &Expression {
         self.second.as_ref()
     }
 
-    pub fn third(&self) -> &dyn Expression {
+    pub fn third(&self) -> &Expression {
         self.third.as_ref()
     }
 }
 
-impl Expression for Ternary {
-    fn accept(&self, visitor: &mut dyn ExpressionVisitor) {
-        visitor.visit_ternary(self);
+impl PartialEq for Ternary {
+    fn eq(&self, other: &Self) -> bool {
+        self.operator.token_type() == other.operator.token_type()
+            && self.first == other.first
+            && self.second == other.second
+            && self.third == other.third
     }
 }
 
-#[derive(Debug)]
+/// Location of a local variable: how many scopes up from the current one and
+/// its index within that scope. Variables without a slot are globals.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Slot {
+    depth: usize,
+    index: usize
+}
+
+impl Slot {
+    pub fn new(depth: usize, index: usize) -> Self {
+        Self {
+            depth,
+            index
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Variable {
-    name: String
+    name: Rc<str>,
+    slot: Option<Slot>
 }
 
 impl Variable {
-    pub fn new(name: String) -> Self {
+    pub fn new(name: Rc<str>) -> Self {
         Self {
-            name
+            name,
+            slot: None
         }
     }
 
-    pub fn name(&self) -> &String {
-        &self.name
+    pub fn local(name: Rc<str>, slot: Slot) -> Self {
+        Self {
+            name,
+            slot: Some(slot)
+        }
     }
-}
 
-impl Expression for Variable {
-    fn accept(&self, visitor: &mut dyn ExpressionVisitor) {
-        visitor.visit_variable(self);
+    pub fn name(&self) -> &Rc<str> {
+        &self.name
     }
 
-    fn as_variable(&self) -> Option<&Variable> {
-        Some(self)
+    pub fn slot(&self) -> Option<Slot> {
+        self.slot
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Assign {
-    name: String,
-    value: Box<dyn Expression>
+    name: Rc<str>,
+    slot: Option<Slot>,
+    value: Box<Expression>
 }
 
 impl Assign {
-    pub fn new(name: String, value: Box<dyn Expression>) -> Self {
+    pub fn new(name: Rc<str>, value: Expression) -> Self {
         Self {
             name,
-            value
+            slot: None,
+            value: Box::new(value)
         }
     }
 
-    pub fn name(&self) -> &String {
+    pub fn local(name: Rc<str>, slot: Slot, value: Expression) -> Self {
+        Self {
+            name,
+            slot: Some(slot),
+            value: Box::new(value)
+        }
+    }
+
+    pub fn name(&self) -> &Rc<str> {
         &self.name
     }
 
-    pub fn value(&self) -> &dyn Expression {
-        self.value.as_ref()
+    pub fn slot(&self) -> Option<Slot> {
+        self.slot
     }
-}
 
-impl Expression for Assign {
-    fn accept(&self, visitor: &mut dyn ExpressionVisitor) {
-        visitor.visit_assign(self);
+    pub fn value(&self) -> &Expression {
+        self.value.as_ref()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Logical {
-    left: Box<dyn Expression>,
+    left: Box<Expression>,
     operator: Token,
-    right: Box<dyn Expression>
+    right: Box<Expression>
 }
 
 impl Logical {
-    pub fn new(left: Box<dyn Expression>, operator: Token, right: Box<dyn Expression>) -> Self {
+    pub fn new(left: Expression, operator: Token, right: Expression) -> Self {
         Self {
-            left,
+            left: Box::new(left),
             operator,
-            right
+            right: Box::new(right)
         }
     }
 
-    pub fn left(&self) -> &dyn Expression {
+    pub fn left(&self) -> &Expression {
         self.left.as_ref()
     }
 
@@ -249,29 +318,149 @@ impl Logical {
         &self.operator
     }
 
-    pub fn right(&self) -> &dyn Expression {
+    pub fn right(&self) -> &Expression {
         self.right.as_ref()
     }
 }
 
-impl Expression for Logical {
-    fn accept(&self, visitor: &mut dyn ExpressionVisitor) {
-        visitor.visit_logical(self);
+impl PartialEq for Logical {
+    fn eq(&self, other: &Self) -> bool {
+        self.operator.token_type() == other.operator.token_type() && self.left == other.left && self.right == other.right
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Interpolation {
+    parts: Vec<Expression>,
+    /// Span and line of each part in the source, so that errors inside
+    /// the braces can point there.
+    locations: Vec<(Span, usize)>
+}
+
+impl Interpolation {
+    pub fn new(parts: Vec<Expression>, locations: Vec<(Span, usize)>) -> Self {
+        Self {
+            parts,
+            locations
+        }
+    }
+
+    /// The same interpolation with its parts replaced one for one.
+    pub fn with_parts(&self, parts: Vec<Expression>) -> Self {
+        Self::new(parts, self.locations.clone())
+    }
+
+    pub fn parts(&self) -> &[Expression] {
+        self.parts.as_ref()
+    }
+
+    pub fn locations(&self) -> &[(Span, usize)] {
+        self.locations.as_ref()
+    }
+}
+
+/// Locations are left out: the same code compares equal wherever it is.
+impl PartialEq for Interpolation {
+    fn eq(&self, other: &Self) -> bool {
+        self.parts == other.parts
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MethodCall {
+    object: Box<Expression>,
+    name: Rc<str>,
+    token: Token,
+    arguments: Vec<Expression>
+}
+
+impl MethodCall {
+    pub fn new(object: Expression, name: Rc<str>, token: Token, arguments: Vec<Expression>) -> Self {
+        Self {
+            object: Box::new(object),
+            name,
+            token,
+            arguments
+        }
+    }
+
+    pub fn object(&self) -> &Expression {
+        self.object.as_ref()
+    }
+
+    pub fn name(&self) -> &Rc<str> {
+        &self.name
+    }
+
+    /// The method name token, kept for its position.
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    pub fn arguments(&self) -> &[Expression] {
+        self.arguments.as_ref()
+    }
+}
+
+/// The name token only gives the position, so it is left out.
+impl PartialEq for MethodCall {
+    fn eq(&self, other: &Self) -> bool {
+        self.object == other.object && self.name == other.name && self.arguments == other.arguments
     }
 }
 
-pub trait ExpressionVisitor: fmt::Debug {
-    fn visit_binary(&mut self, expression: &Binary);
-    fn visit_grouping(&mut self, expression: &Grouping);
-    fn visit_literal(&mut self, expression: &Literal);
-    fn visit_unary(&mut self, expression: &Unary);
-    fn visit_ternary(&mut self, expression: &Ternary);
-    fn visit_variable(&mut self, expression: &Variable);
-    fn visit_assign(&mut self, expression: &Assign);
-    fn visit_logical(&mut self, expression: &Logical);
+/// Call of a native function, such as `clock()`.
+#[derive(Debug, Clone)]
+pub struct Call {
+    name: Rc<str>,
+    token: Token,
+    arguments: Vec<Expression>
 }
 
-#[derive(Debug)]
+impl Call {
+    pub fn new(name: Rc<str>, token: Token, arguments: Vec<Expression>) -> Self {
+        Self {
+            name,
+            token,
+            arguments
+        }
+    }
+
+    pub fn name(&self) -> &Rc<str> {
+        &self.name
+    }
+
+    /// The function name token, kept for its position.
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    pub fn arguments(&self) -> &[Expression] {
+        self.arguments.as_ref()
+    }
+}
+
+impl PartialEq for Call {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.arguments == other.arguments
+    }
+}
+
+pub trait ExpressionVisitor<T> {
+    fn visit_binary(&mut self, expression: &Binary) -> T;
+    fn visit_grouping(&mut self, expression: &Grouping) -> T;
+    fn visit_literal(&mut self, expression: &Literal) -> T;
+    fn visit_unary(&mut self, expression: &Unary) -> T;
+    fn visit_ternary(&mut self, expression: &Ternary) -> T;
+    fn visit_variable(&mut self, expression: &Variable) -> T;
+    fn visit_assign(&mut self, expression: &Assign) -> T;
+    fn visit_logical(&mut self, expression: &Logical) -> T;
+    fn visit_interpolation(&mut self, expression: &Interpolation) -> T;
+    fn visit_method_call(&mut self, expression: &MethodCall) -> T;
+    fn visit_call(&mut self, expression: &Call) -> T;
+}
+
+#[derive(Debug, Default)]
 pub struct Printer;
 
 impl Printer {
@@ -279,236 +468,360 @@ impl Printer {
         Self
     }
 
-    pub fn print(&mut self, expression: &dyn Expression) {
-        expression.accept(self);
+    pub fn print(&mut self, expression: &Expression) -> String {
+        expression.accept(self)
+    }
+
+    pub fn print_statement(&mut self, statement: &Statement) -> String {
+        statement.accept(self)
+    }
+
+    fn parenthesize(&mut self, name: &str, expressions: &[&Expression]) -> String {
+        let mut result = format!("({name}");
+
+        for expression in expressions {
+            result.push(' ');
+            result.push_str(&expression.accept(self));
+        }
+
+        result.push(')');
+        result
     }
 }
 
-impl ExpressionVisitor for Printer {
-    fn visit_binary(&mut self, expression: &Binary) {
-        print!("({} ", expression.operator);
-        expression.left.accept(self);
-        print!(" ");
-        expression.right.accept(self);
-        print!(")");
+impl ExpressionVisitor<String> for Printer {
+    fn visit_binary(&mut self, expression: &Binary) -> String {
+        self.parenthesize(&expression.operator.to_string(), &[expression.left(), expression.right()])
+    }
+
+    fn visit_grouping(&mut self, expression: &Grouping) -> String {
+        self.parenthesize("group", &[expression.expression()])
+    }
+
+    fn visit_literal(&mut self, expression: &Literal) -> String {
+        expression.value.to_string()
+    }
+
+    fn visit_unary(&mut self, expression: &Unary) -> String {
+        self.parenthesize(&expression.operator.to_string(), &[expression.right()])
     }
 
-    fn visit_grouping(&mut self, expression: &Grouping) {
-        print!("(group ");
-        expression.expression.accept(self);
-        print!(")");
+    fn visit_ternary(&mut self, expression: &Ternary) -> String {
+        self.parenthesize(
+            &expression.operator.to_string(),
+            &[expression.first(), expression.second(), expression.third()]
+        )
     }
 
-    fn visit_literal(&mut self, expression: &Literal) {
-        print!("{}", expression.value);
+    fn visit_variable(&mut self, expression: &Variable) -> String {
+        format!("(variable \"{}\")", expression.name)
     }
 
-    fn visit_unary(&mut self, expression: &Unary) {
-        print!("({} ", expression.operator);
-        expression.right.accept(self);
-        print!(")")
+    fn visit_assign(&mut self, expression: &Assign) -> String {
+        self.parenthesize(&format!("assign \"{}\"", expression.name), &[expression.value()])
     }
-    
-    fn visit_ternary(&mut self, expression: &Ternary) {
-        print!("({} ", expression.operator);
-        expression.first.accept(self);
-        print!(" ");
-        expression.second.accept(self);
-        print!(" ");
-        expression.third.accept(self);
-        print!(")");
+
+    fn visit_logical(&mut self, expression: &Logical) -> String {
+        self.parenthesize(&expression.operator.to_string(), &[expression.left(), expression.right()])
     }
-    
-    fn visit_variable(&mut self, expression: &Variable) {
-        print!("(variable \"{}\")", expression.name);
+
+    fn visit_interpolation(&mut self, expression: &Interpolation) -> String {
+        let parts: Vec<&Expression> = expression.parts.iter().collect();
+        self.parenthesize("interpolation", &parts)
     }
-    
-    fn visit_assign(&mut self, expression: &Assign) {
-        print!("(assign \"{}\" ", expression.name);
-        expression.value.accept(self);
-        print!(")");
+
+    fn visit_method_call(&mut self, expression: &MethodCall) -> String {
+        let mut parts: Vec<&Expression> = vec![expression.object()];
+        parts.extend(expression.arguments.iter());
+        self.parenthesize(&format!("call .{}", expression.name), &parts)
     }
-    
-    fn visit_logical(&mut self, expression: &Logical) {
-        print!("({} ", expression.operator);
-        expression.left.accept(self);
-        print!(" ");
-        expression.right.accept(self);
-        print!(")");
+
+    fn visit_call(&mut self, expression: &Call) -> String {
+        let arguments: Vec<&Expression> = expression.arguments.iter().collect();
+        self.parenthesize(&format!("call {}", expression.name), &arguments)
     }
 }
 
-pub trait Statement: fmt::Debug {
-    fn accept(&self, visitor: &mut dyn StatementVisitor);
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    ExpressionStatement(ExpressionStatement),
+    Print(Print),
+    Var(Var),
+    Block(Block),
+    If(If),
+    While(While)
 }
 
-#[derive(Debug)]
+impl Statement {
+    pub fn accept<T>(&self, visitor: &mut dyn StatementVisitor<T>) -> T {
+        use Statement::*;
+
+        match self {
+            ExpressionStatement(statement) => visitor.visit_expression_statement(statement),
+            Print(statement) => visitor.visit_print(statement),
+            Var(statement) => visitor.visit_var(statement),
+            Block(statement) => visitor.visit_block(statement),
+            If(statement) => visitor.visit_if(statement),
+            While(statement) => visitor.visit_while(statement)
+        }
+    }
+}
+
+/// Statements keep the line they start on, which is left out when they are
+/// compared.
+#[derive(Debug, Clone)]
 pub struct ExpressionStatement {
-    expression: Box<dyn Expression>,
+    expression: Expression,
+    line: usize
 }
 
 impl ExpressionStatement {
-    pub fn new(expression: Box<dyn Expression>) -> Self {
+    pub fn new(expression: Expression, line: usize) -> Self {
         Self {
-            expression
+            expression,
+            line
         }
     }
 
-    pub fn expression(&self) -> &dyn Expression {
-        self.expression.as_ref()
+    pub fn expression(&self) -> &Expression {
+        &self.expression
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
     }
 }
 
-impl Statement for ExpressionStatement {
-    fn accept(&self, visitor: &mut dyn StatementVisitor) {
-        visitor.visit_expression_statement(self);
+impl PartialEq for ExpressionStatement {
+    fn eq(&self, other: &Self) -> bool {
+        self.expression == other.expression
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Print {
-    expression: Box<dyn Expression>,
+    expression: Expression,
+    line: usize
 }
 
 impl Print {
-    pub fn new(expression: Box<dyn Expression>) -> Self {
+    pub fn new(expression: Expression, line: usize) -> Self {
         Self {
-            expression
+            expression,
+            line
         }
     }
 
-    pub fn expression(&self) -> &dyn Expression {
-        self.expression.as_ref()
+    pub fn expression(&self) -> &Expression {
+        &self.expression
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
     }
 }
 
-impl Statement for Print {
-    fn accept(&self, visitor: &mut dyn StatementVisitor) {
-        visitor.visit_print(self);
+impl PartialEq for Print {
+    fn eq(&self, other: &Self) -> bool {
+        self.expression == other.expression
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Var {
-    name: String,
-    right: Box<dyn Expression>
+    name: Rc<str>,
+    slot: Option<usize>,
+    right: Expression,
+    line: usize
 }
 
 impl Var {
-    pub fn new(name: String, right: Box<dyn Expression>) -> Self {
+    pub fn new(name: Rc<str>, right: Expression, line: usize) -> Self {
+        Self {
+            name,
+            slot: None,
+            right,
+            line
+        }
+    }
+
+    pub fn local(name: Rc<str>, slot: usize, right: Expression, line: usize) -> Self {
         Self {
             name,
-            right
+            slot: Some(slot),
+            right,
+            line
         }
     }
 
-    pub fn name(&self) -> &String {
+    pub fn name(&self) -> &Rc<str> {
         &self.name
     }
 
-    pub fn right(&self) -> &dyn Expression {
-        self.right.as_ref()
+    /// Index in the enclosing block's scope, or `None` for a global.
+    pub fn slot(&self) -> Option<usize> {
+        self.slot
+    }
+
+    pub fn right(&self) -> &Expression {
+        &self.right
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
     }
 }
 
-impl Statement for Var {
-    fn accept(&self, visitor: &mut dyn StatementVisitor) {
-        visitor.visit_var(self);
+impl PartialEq for Var {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.slot == other.slot && self.right == other.right
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Block {
-    statements: Vec<Box<dyn Statement>>,
+    statements: Vec<Statement>,
 }
 
 impl Block {
-    pub fn new(statements: Vec<Box<dyn Statement>>) -> Self {
+    pub fn new(statements: Vec<Statement>) -> Self {
         Self {
             statements
         }
     }
 
-    pub fn statements(&self) -> &[Box<dyn Statement>] {
+    pub fn statements(&self) -> &[Statement] {
         self.statements.as_ref()
     }
 }
 
-impl Statement for Block {
-    fn accept(&self, visitor: &mut dyn StatementVisitor) {
-        visitor.visit_block(self);
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct If {
-    condition: Box<dyn Expression>,
-    then_branch: Box<dyn Statement>,
-    else_branch: Option<Box<dyn Statement>>
+    condition: Expression,
+    then_branch: Box<Statement>,
+    else_branch: Option<Box<Statement>>,
+    line: usize
 }
 
 impl If {
-    pub fn new(condition: Box<dyn Expression>, then_branch: Box<dyn Statement>, else_branch: Option<Box<dyn Statement>>) -> Self {
+    pub fn new(condition: Expression, then_branch: Statement, else_branch: Option<Statement>, line: usize) -> Self {
         Self {
             condition,
-            then_branch,
-            else_branch
+            then_branch: Box::new(then_branch),
+            else_branch: else_branch.map(Box::new),
+            line
         }
     }
 
-    pub fn condition(&self) -> &dyn Expression {
-        self.condition.as_ref()
+    pub fn condition(&self) -> &Expression {
+        &self.condition
     }
 
-    pub fn then_branch(&self) -> &dyn Statement {
+    pub fn then_branch(&self) -> &Statement {
         self.then_branch.as_ref()
     }
 
-    pub fn else_branch(&self) -> Option<&dyn Statement> {
-        self.else_branch.as_ref().map(Box::as_ref)
+    pub fn else_branch(&self) -> Option<&Statement> {
+        self.else_branch.as_deref()
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
     }
 }
 
-impl Statement for If {
-    fn accept(&self, visitor: &mut dyn StatementVisitor) {
-        visitor.visit_if(self);
+impl PartialEq for If {
+    fn eq(&self, other: &Self) -> bool {
+        self.condition == other.condition && self.then_branch == other.then_branch && self.else_branch == other.else_branch
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct While {
-    condition: Box<dyn Expression>,
-    body: Box<dyn Statement>
+    condition: Expression,
+    body: Box<Statement>,
+    line: usize
 }
 
 impl While {
-    pub fn new(condition: Box<dyn Expression>, body: Box<dyn Statement>) -> Self {
+    pub fn new(condition: Expression, body: Statement, line: usize) -> Self {
         Self {
             condition,
-            body
+            body: Box::new(body),
+            line
         }
     }
 
-    pub fn condition(&self) -> &dyn Expression {
-        self.condition.as_ref()
+    pub fn condition(&self) -> &Expression {
+        &self.condition
     }
 
-    pub fn body(&self) -> &dyn Statement {
+    pub fn body(&self) -> &Statement {
         self.body.as_ref()
     }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
 }
 
-impl Statement for While {
-    fn accept(&self, visitor: &mut dyn StatementVisitor) {
-        visitor.visit_while(self);
+impl PartialEq for While {
+    fn eq(&self, other: &Self) -> bool {
+        self.condition == other.condition && self.body == other.body
     }
 }
 
-pub trait StatementVisitor: fmt::Debug {
-    fn visit_expression_statement(&mut self, statement: &ExpressionStatement);
-    fn visit_print(&mut self, statement: &Print);
-    fn visit_var(&mut self, statement: &Var);
-    fn visit_block(&mut self, statement: &Block);
-    fn visit_if(&mut self, statement: &If);
-    fn visit_while(&mut self, statement: &While);
+pub trait StatementVisitor<T> {
+    fn visit_expression_statement(&mut self, statement: &ExpressionStatement) -> T;
+    fn visit_print(&mut self, statement: &Print) -> T;
+    fn visit_var(&mut self, statement: &Var) -> T;
+    fn visit_block(&mut self, statement: &Block) -> T;
+    fn visit_if(&mut self, statement: &If) -> T;
+    fn visit_while(&mut self, statement: &While) -> T;
+}
+
+impl StatementVisitor<String> for Printer {
+    fn visit_expression_statement(&mut self, statement: &ExpressionStatement) -> String {
+        self.parenthesize(";", &[statement.expression()])
+    }
+
+    fn visit_print(&mut self, statement: &Print) -> String {
+        self.parenthesize("print", &[statement.expression()])
+    }
+
+    fn visit_var(&mut self, statement: &Var) -> String {
+        self.parenthesize(&format!("var \"{}\"", statement.name), &[statement.right()])
+    }
+
+    fn visit_block(&mut self, statement: &Block) -> String {
+        let mut result = "(block".to_string();
+
+        for statement in statement.statements() {
+            result.push(' ');
+            result.push_str(&statement.accept(self));
+        }
+
+        result.push(')');
+        result
+    }
+
+    fn visit_if(&mut self, statement: &If) -> String {
+        let mut result = format!(
+            "(if {} {}",
+            statement.condition().accept(self),
+            statement.then_branch().accept(self)
+        );
+
+        if let Some(else_branch) = statement.else_branch() {
+            result.push(' ');
+            result.push_str(&else_branch.accept(self));
+        }
+
+        result.push(')');
+        result
+    }
+
+    fn visit_while(&mut self, statement: &While) -> String {
+        format!("(while {} {})", statement.condition().accept(self), statement.body().accept(self))
+    }
 }
@@ -0,0 +1,153 @@
+use std::{fmt, io, str};
+
+use super::{
+    dialect::Dialect,
+    error::Error,
+    interpreter::Interpreter,
+    heap::GcStats,
+    limits::{Limits, CancelHandle},
+    capabilities::Capabilities,
+    vm::Vm
+};
+
+/// Execution strategy used to run programs.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// Evaluate the syntax tree directly.
+    #[default]
+    TreeWalk,
+    /// Compile to bytecode and run it on the stack `Vm`.
+    Bytecode
+}
+
+/// Something that can run source code, keeping its state between runs.
+///
+/// Runs recurse once or more per level of nesting in the program. At the
+/// default `parser::MAX_DEPTH` they fit a standard 2 MiB thread stack; a
+/// larger `set_max_depth` needs up to 64 KiB of stack per level.
+pub trait Executor {
+    fn run(&mut self, code: &str) -> Result<(), Error>;
+    fn set_optimize(&mut self, optimize: bool);
+    fn set_max_depth(&mut self, max_depth: usize);
+    fn set_limits(&mut self, limits: Limits);
+    fn cancel_handle(&self) -> CancelHandle;
+    fn set_capabilities(&mut self, capabilities: Capabilities);
+    fn set_deterministic(&mut self, seed: Option<u64>);
+    fn set_output(&mut self, output: Box<dyn io::Write>);
+    fn gc_stats(&self) -> GcStats;
+    fn collect_garbage(&mut self) -> usize;
+}
+
+impl Backend {
+    pub fn executor(self, dialect: Dialect) -> Box<dyn Executor> {
+        match self {
+            Backend::TreeWalk => Box::new(Interpreter::with_dialect(dialect)),
+            Backend::Bytecode => Box::new(Vm::with_dialect(dialect))
+        }
+    }
+}
+
+impl Executor for Interpreter {
+    fn run(&mut self, code: &str) -> Result<(), Error> {
+        Interpreter::run(self, code)
+    }
+
+    fn set_optimize(&mut self, optimize: bool) {
+        Interpreter::set_optimize(self, optimize)
+    }
+
+    fn set_max_depth(&mut self, max_depth: usize) {
+        Interpreter::set_max_depth(self, max_depth)
+    }
+
+    fn set_limits(&mut self, limits: Limits) {
+        Interpreter::set_limits(self, limits)
+    }
+
+    fn cancel_handle(&self) -> CancelHandle {
+        Interpreter::cancel_handle(self)
+    }
+
+    fn set_capabilities(&mut self, capabilities: Capabilities) {
+        Interpreter::set_capabilities(self, capabilities)
+    }
+
+    fn set_deterministic(&mut self, seed: Option<u64>) {
+        Interpreter::set_deterministic(self, seed)
+    }
+
+    fn set_output(&mut self, output: Box<dyn io::Write>) {
+        Interpreter::set_output(self, output)
+    }
+
+    fn gc_stats(&self) -> GcStats {
+        Interpreter::gc_stats(self)
+    }
+
+    fn collect_garbage(&mut self) -> usize {
+        Interpreter::collect_garbage(self)
+    }
+}
+
+impl Executor for Vm {
+    fn run(&mut self, code: &str) -> Result<(), Error> {
+        Vm::run(self, code)
+    }
+
+    fn set_optimize(&mut self, optimize: bool) {
+        Vm::set_optimize(self, optimize)
+    }
+
+    fn set_max_depth(&mut self, max_depth: usize) {
+        Vm::set_max_depth(self, max_depth)
+    }
+
+    fn set_limits(&mut self, limits: Limits) {
+        Vm::set_limits(self, limits)
+    }
+
+    fn cancel_handle(&self) -> CancelHandle {
+        Vm::cancel_handle(self)
+    }
+
+    fn set_capabilities(&mut self, capabilities: Capabilities) {
+        Vm::set_capabilities(self, capabilities)
+    }
+
+    fn set_deterministic(&mut self, seed: Option<u64>) {
+        Vm::set_deterministic(self, seed)
+    }
+
+    fn set_output(&mut self, output: Box<dyn io::Write>) {
+        Vm::set_output(self, output)
+    }
+
+    fn gc_stats(&self) -> GcStats {
+        Vm::gc_stats(self)
+    }
+
+    fn collect_garbage(&mut self) -> usize {
+        Vm::collect_garbage(self)
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::TreeWalk => write!(f, "tree"),
+            Backend::Bytecode => write!(f, "bytecode")
+        }
+    }
+}
+
+impl str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tree" => Ok(Backend::TreeWalk),
+            "bytecode" => Ok(Backend::Bytecode),
+            _ => Err(format!("unknown backend \"{s}\", expected \"tree\" or \"bytecode\""))
+        }
+    }
+}
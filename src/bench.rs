@@ -0,0 +1,60 @@
+use std::time::{Duration, Instant};
+
+/// Wall time statistics over several runs of one benchmark.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Summary {
+    runs: usize,
+    min: Duration,
+    median: Duration,
+    max: Duration
+}
+
+impl Summary {
+    pub fn new(samples: &[Duration]) -> Self {
+        let mut samples = samples.to_vec();
+        samples.sort();
+
+        let median = match samples.len() {
+            0 => Duration::ZERO,
+            len if len % 2 == 0 => (samples[len / 2 - 1] + samples[len / 2]) / 2,
+            len => samples[len / 2]
+        };
+
+        Self {
+            runs: samples.len(),
+            min: samples.first().copied().unwrap_or_default(),
+            median,
+            max: samples.last().copied().unwrap_or_default()
+        }
+    }
+
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    pub fn min(&self) -> Duration {
+        self.min
+    }
+
+    pub fn median(&self) -> Duration {
+        self.median
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+}
+
+/// Calls `run` `iterations` times and summarizes how long each call took.
+/// Stops at the first error.
+pub fn measure<E>(iterations: usize, mut run: impl FnMut() -> Result<(), E>) -> Result<Summary, E> {
+    let mut samples = Vec::with_capacity(iterations);
+
+    for _ in 0..iterations {
+        let start = Instant::now();
+        run()?;
+        samples.push(start.elapsed());
+    }
+
+    Ok(Summary::new(&samples))
+}
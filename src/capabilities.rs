@@ -0,0 +1,112 @@
+use std::{fmt, str};
+
+use super::error::{Error, ErrorKind};
+
+/// Access to the world outside the interpreter that a native facility may
+/// need. Natives check for their capability before doing anything.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Capability {
+    Files,
+    Env,
+    Clock,
+    Exit,
+    Stdin
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] = [
+        Capability::Files,
+        Capability::Env,
+        Capability::Clock,
+        Capability::Exit,
+        Capability::Stdin
+    ];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::Files => write!(f, "files"),
+            Capability::Env => write!(f, "env"),
+            Capability::Clock => write!(f, "clock"),
+            Capability::Exit => write!(f, "exit"),
+            Capability::Stdin => write!(f, "stdin")
+        }
+    }
+}
+
+impl str::FromStr for Capability {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Capability::ALL
+            .into_iter()
+            .find(|capability| capability.to_string() == s)
+            .ok_or_else(|| {
+                let names: Vec<String> = Capability::ALL.iter().map(|capability| format!("\"{capability}\"")).collect();
+                format!("unknown capability \"{s}\", expected one of {}", names.join(", "))
+            })
+    }
+}
+
+/// The set of capabilities an executor was granted. Empty by default, so
+/// embedders have to opt in to anything that leaves the sandbox.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    granted: u8
+}
+
+impl Capabilities {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn all() -> Self {
+        Capability::ALL.into_iter().collect()
+    }
+
+    pub fn grant(&mut self, capability: Capability) {
+        self.granted |= capability.bit();
+    }
+
+    pub fn revoke(&mut self, capability: Capability) {
+        self.granted &= !capability.bit();
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.granted & capability.bit() != 0
+    }
+
+    /// Fails with a "permission denied" runtime error unless `capability`
+    /// was granted. `facility` names what the script tried to use.
+    pub fn check(&self, capability: Capability, facility: &str) -> Result<(), Error> {
+        if self.allows(capability) {
+            return Ok(());
+        }
+
+        Err(
+            Error::new(
+                ErrorKind::RuntimeError {
+                    message: format!("Permission denied: \"{facility}\" needs the \"{capability}\" capability")
+                }
+            )
+        )
+    }
+}
+
+impl FromIterator<Capability> for Capabilities {
+    fn from_iter<I: IntoIterator<Item = Capability>>(iter: I) -> Self {
+        let mut capabilities = Capabilities::none();
+
+        for capability in iter {
+            capabilities.grant(capability);
+        }
+
+        capabilities
+    }
+}
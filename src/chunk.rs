@@ -0,0 +1,192 @@
+use std::{collections::HashMap, fmt, ops::Range, rc::Rc};
+
+use super::{value::Value, token::Span};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OpCode {
+    Constant,
+    Null,
+    True,
+    False,
+    Pop,
+    PopN,
+    DefineGlobal,
+    GetGlobal,
+    SetGlobal,
+    GetLocal,
+    SetLocal,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    Negate,
+    Not,
+    Print,
+    Jump,
+    JumpIfFalse,
+    JumpIfTrue,
+    Loop,
+    Interpolate,
+    Invoke,
+    CallNative
+}
+
+impl OpCode {
+    const ALL: [OpCode; 31] = [
+        OpCode::Constant,
+        OpCode::Null,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::PopN,
+        OpCode::DefineGlobal,
+        OpCode::GetGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Equal,
+        OpCode::NotEqual,
+        OpCode::Negate,
+        OpCode::Not,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::JumpIfTrue,
+        OpCode::Loop,
+        OpCode::Interpolate,
+        OpCode::Invoke,
+        OpCode::CallNative
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        Self::ALL.get(byte as usize).copied()
+    }
+
+    /// Number of operand bytes following the opcode.
+    pub fn operand_size(&self) -> usize {
+        use OpCode::*;
+
+        match self {
+            Constant | PopN | DefineGlobal | GetGlobal | SetGlobal | GetLocal | SetLocal
+                | Jump | JumpIfFalse | JumpIfTrue | Loop | Interpolate => 2,
+            Invoke | CallNative => 3,
+            _ => 0
+        }
+    }
+}
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Constants that are looked up by value, so that each is stored once.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Number(u64),
+    String(Rc<str>)
+}
+
+/// Compiled program: bytecode, the constants it refers to and a run-length
+/// encoded table mapping code offsets to source lines.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    code: Vec<u8>,
+    constants: Vec<Value>,
+    constant_indexes: HashMap<ConstantKey, usize>,
+    lines: Vec<(usize, usize)>,
+    /// Code ranges that runtime errors are reported at, innermost first.
+    locations: Vec<(Range<usize>, Span, usize)>
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    pub fn constants(&self) -> &[Value] {
+        &self.constants
+    }
+
+    pub fn constant(&self, index: usize) -> &Value {
+        &self.constants[index]
+    }
+
+    pub fn line(&self, offset: usize) -> usize {
+        match self.lines.partition_point(|(start, _)| *start <= offset) {
+            0 => 0,
+            index => self.lines[index - 1].1
+        }
+    }
+
+    /// Where a runtime error in the instruction at `offset` happened, if
+    /// the code there was given a location.
+    pub fn location(&self, offset: usize) -> Option<(Span, usize)> {
+        self.locations.iter()
+            .find(|(range, _, _)| range.contains(&offset))
+            .map(|(_, span, line)| (*span, *line))
+    }
+
+    /// Reports runtime errors in the code written since `start` at `span`.
+    pub fn locate(&mut self, start: usize, span: Span, line: usize) {
+        self.locations.push((start..self.code.len(), span, line));
+    }
+
+    pub fn write(&mut self, byte: u8, line: usize) {
+        if self.lines.last().map(|(_, last)| *last) != Some(line) {
+            self.lines.push((self.code.len(), line));
+        }
+
+        self.code.push(byte);
+    }
+
+    pub fn patch(&mut self, offset: usize, byte: u8) {
+        self.code[offset] = byte;
+    }
+
+    /// Returns the index of `value` in the constant pool, adding it if needed.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        let key = match &value {
+            Value::Number(number) => Some(ConstantKey::Number(number.to_bits())),
+            Value::String(string) => Some(ConstantKey::String(string.clone())),
+            _ => None
+        };
+
+        if let Some(index) = key.as_ref().and_then(|key| self.constant_indexes.get(key)) {
+            return *index;
+        }
+
+        let index = self.constants.len();
+        self.constants.push(value);
+
+        if let Some(key) = key {
+            self.constant_indexes.insert(key, index);
+        }
+
+        index
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+}
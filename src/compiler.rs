@@ -0,0 +1,410 @@
+use super::{
+    chunk::{Chunk, OpCode},
+    error::{Error, ErrorKind},
+    value::Value,
+    token::{Token, TokenType},
+    ast::{
+        ExpressionVisitor,
+        StatementVisitor,
+        Expression,
+        Statement,
+        Binary,
+        Grouping,
+        Literal,
+        Unary,
+        Ternary,
+        Slot,
+        Variable,
+        Assign,
+        Logical,
+        Interpolation,
+        MethodCall,
+        Call,
+        ExpressionStatement,
+        Print,
+        Var,
+        Block,
+        If,
+        While
+    }
+};
+
+type CompileResult = Result<(), Error>;
+
+/// Compiles a resolved program into a `Chunk` for the stack `Vm`.
+///
+/// Locals live on the VM stack: a block's locals start right after the locals
+/// of the blocks enclosing it, so a resolver `Slot` maps to a fixed stack index.
+#[derive(Debug)]
+pub struct Compiler {
+    chunk: Chunk,
+    scopes: Vec<Scope>,
+    line: usize
+}
+
+#[derive(Debug)]
+struct Scope {
+    base: usize,
+    count: usize
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self {
+            chunk: Chunk::new(),
+            scopes: Vec::new(),
+            line: 1
+        }
+    }
+
+    pub fn compile(mut self, statements: &[Statement]) -> Result<Chunk, Error> {
+        for statement in statements {
+            self.compile_statement(statement)?;
+        }
+
+        Ok(self.chunk)
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> CompileResult {
+        statement.accept(self)
+    }
+
+    fn compile_expression(&mut self, expression: &Expression) -> CompileResult {
+        expression.accept(self)
+    }
+
+    fn mark(&mut self, token: &Token) {
+        self.line = token.line();
+    }
+
+    /// Sets the line of the code emitted next, where the node knows it.
+    fn mark_line(&mut self, line: usize) {
+        if line > 0 {
+            self.line = line;
+        }
+    }
+
+    fn emit(&mut self, op_code: OpCode) {
+        self.chunk.write(op_code as u8, self.line);
+    }
+
+    fn emit_u16(&mut self, op_code: OpCode, operand: usize) -> CompileResult {
+        let operand = u16::try_from(operand)
+            .map_err(|_| compile_error(format!("Operand of {op_code} does not fit in 16 bits")))?;
+
+        self.emit(op_code);
+
+        for byte in operand.to_be_bytes() {
+            self.chunk.write(byte, self.line);
+        }
+
+        Ok(())
+    }
+
+    fn emit_constant(&mut self, op_code: OpCode, value: Value) -> CompileResult {
+        let index = self.chunk.add_constant(value);
+
+        if index > u16::MAX as usize {
+            return Err(compile_error("Too many constants in one chunk".into()));
+        }
+
+        self.emit_u16(op_code, index)
+    }
+
+    fn emit_jump(&mut self, op_code: OpCode) -> usize {
+        self.emit(op_code);
+        self.chunk.write(0xff, self.line);
+        self.chunk.write(0xff, self.line);
+
+        self.chunk.code().len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize) -> CompileResult {
+        let jump = u16::try_from(self.chunk.code().len() - offset - 2)
+            .map_err(|_| compile_error("Too much code to jump over".into()))?;
+
+        let [high, low] = jump.to_be_bytes();
+        self.chunk.patch(offset, high);
+        self.chunk.patch(offset + 1, low);
+
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, start: usize) -> CompileResult {
+        let jump = self.chunk.code().len() + 3 - start;
+        let jump = u16::try_from(jump).map_err(|_| compile_error("Loop body too large".into()))?;
+
+        self.emit_u16(OpCode::Loop, jump as usize)
+    }
+
+    fn stack_index(&self, slot: Slot) -> usize {
+        let scope = &self.scopes[self.scopes.len() - 1 - slot.depth()];
+
+        scope.base + slot.index()
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExpressionVisitor<CompileResult> for Compiler {
+    fn visit_binary(&mut self, expression: &Binary) -> CompileResult {
+        self.compile_expression(expression.left())?;
+
+        if expression.operator().token_type() == TokenType::Comma {
+            self.emit(OpCode::Pop);
+            return self.compile_expression(expression.right());
+        }
+
+        self.compile_expression(expression.right())?;
+        self.mark(expression.operator());
+
+        use TokenType::*;
+
+        let op_code = match expression.operator().token_type() {
+            Plus => OpCode::Add,
+            Minus => OpCode::Subtract,
+            Star => OpCode::Multiply,
+            Slash => OpCode::Divide,
+            Greater => OpCode::Greater,
+            GreaterEqual => OpCode::GreaterEqual,
+            Less => OpCode::Less,
+            LessEqual => OpCode::LessEqual,
+            EqualEqual => OpCode::Equal,
+            BangEqual => OpCode::NotEqual,
+            _ => unreachable!()
+        };
+
+        self.emit(op_code);
+
+        Ok(())
+    }
+
+    fn visit_grouping(&mut self, expression: &Grouping) -> CompileResult {
+        self.compile_expression(expression.expression())
+    }
+
+    fn visit_literal(&mut self, expression: &Literal) -> CompileResult {
+        self.mark_line(expression.line());
+
+        match expression.value() {
+            Value::Null => self.emit(OpCode::Null),
+            Value::True => self.emit(OpCode::True),
+            Value::False => self.emit(OpCode::False),
+            value => self.emit_constant(OpCode::Constant, value.clone())?
+        }
+
+        Ok(())
+    }
+
+    fn visit_unary(&mut self, expression: &Unary) -> CompileResult {
+        self.compile_expression(expression.right())?;
+        self.mark(expression.operator());
+
+        match expression.operator().token_type() {
+            TokenType::Minus => self.emit(OpCode::Negate),
+            TokenType::Bang => self.emit(OpCode::Not),
+            _ => unreachable!()
+        }
+
+        Ok(())
+    }
+
+    fn visit_ternary(&mut self, expression: &Ternary) -> CompileResult {
+        self.compile_expression(expression.first())?;
+        self.mark(expression.operator());
+
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit(OpCode::Pop);
+        self.compile_expression(expression.second())?;
+
+        let end_jump = self.emit_jump(OpCode::Jump);
+        self.patch_jump(else_jump)?;
+        self.emit(OpCode::Pop);
+        self.compile_expression(expression.third())?;
+        self.patch_jump(end_jump)
+    }
+
+    fn visit_variable(&mut self, expression: &Variable) -> CompileResult {
+        match expression.slot() {
+            Some(slot) => self.emit_u16(OpCode::GetLocal, self.stack_index(slot)),
+            None => self.emit_constant(OpCode::GetGlobal, Value::String(expression.name().clone()))
+        }
+    }
+
+    fn visit_assign(&mut self, expression: &Assign) -> CompileResult {
+        self.compile_expression(expression.value())?;
+
+        match expression.slot() {
+            Some(slot) => self.emit_u16(OpCode::SetLocal, self.stack_index(slot)),
+            None => self.emit_constant(OpCode::SetGlobal, Value::String(expression.name().clone()))
+        }
+    }
+
+    fn visit_logical(&mut self, expression: &Logical) -> CompileResult {
+        self.compile_expression(expression.left())?;
+        self.mark(expression.operator());
+
+        let op_code = match expression.operator().token_type() {
+            TokenType::And => OpCode::JumpIfFalse,
+            TokenType::Or => OpCode::JumpIfTrue,
+            _ => unreachable!()
+        };
+
+        let end_jump = self.emit_jump(op_code);
+        self.emit(OpCode::Pop);
+        self.compile_expression(expression.right())?;
+        self.patch_jump(end_jump)
+    }
+
+    fn visit_interpolation(&mut self, expression: &Interpolation) -> CompileResult {
+        for (part, (span, line)) in expression.parts().iter().zip(expression.locations()) {
+            let start = self.chunk.code().len();
+            self.compile_expression(part)?;
+            self.chunk.locate(start, *span, *line);
+        }
+
+        self.emit_u16(OpCode::Interpolate, expression.parts().len())
+    }
+
+    fn visit_method_call(&mut self, expression: &MethodCall) -> CompileResult {
+        self.compile_expression(expression.object())?;
+
+        for argument in expression.arguments() {
+            self.compile_expression(argument)?;
+        }
+
+        let count = u8::try_from(expression.arguments().len())
+            .map_err(|_| compile_error("Can't have more than 255 arguments".into()))?;
+
+        self.mark(expression.token());
+
+        let name = Value::String(expression.name().clone());
+        let index = self.chunk.add_constant(name);
+
+        self.emit_u16(OpCode::Invoke, index)?;
+        self.chunk.write(count, self.line);
+
+        Ok(())
+    }
+
+    fn visit_call(&mut self, expression: &Call) -> CompileResult {
+        for argument in expression.arguments() {
+            self.compile_expression(argument)?;
+        }
+
+        let count = u8::try_from(expression.arguments().len())
+            .map_err(|_| compile_error("Can't have more than 255 arguments".into()))?;
+
+        self.mark(expression.token());
+
+        let name = Value::String(expression.name().clone());
+        let index = self.chunk.add_constant(name);
+
+        self.emit_u16(OpCode::CallNative, index)?;
+        self.chunk.write(count, self.line);
+
+        Ok(())
+    }
+}
+
+impl StatementVisitor<CompileResult> for Compiler {
+    fn visit_expression_statement(&mut self, statement: &ExpressionStatement) -> CompileResult {
+        self.mark_line(statement.line());
+        self.compile_expression(statement.expression())?;
+        self.emit(OpCode::Pop);
+
+        Ok(())
+    }
+
+    fn visit_print(&mut self, statement: &Print) -> CompileResult {
+        self.mark_line(statement.line());
+        self.compile_expression(statement.expression())?;
+        self.emit(OpCode::Print);
+
+        Ok(())
+    }
+
+    fn visit_var(&mut self, statement: &Var) -> CompileResult {
+        self.mark_line(statement.line());
+        self.compile_expression(statement.right())?;
+
+        let index = match statement.slot() {
+            Some(index) => index,
+            None => return self.emit_constant(OpCode::DefineGlobal, Value::String(statement.name().clone()))
+        };
+
+        let scope = self.scopes.last_mut().expect("local declared outside of a block");
+
+        if index < scope.count {
+            let stack_index = scope.base + index;
+            self.emit_u16(OpCode::SetLocal, stack_index)?;
+            self.emit(OpCode::Pop);
+        } else {
+            scope.count += 1;
+        }
+
+        Ok(())
+    }
+
+    fn visit_block(&mut self, statement: &Block) -> CompileResult {
+        let base = self.scopes.last().map(|scope| scope.base + scope.count).unwrap_or(0);
+        self.scopes.push(Scope { base, count: 0 });
+
+        for statement in statement.statements() {
+            self.compile_statement(statement)?;
+        }
+
+        let scope = self.scopes.pop().unwrap();
+
+        if scope.count > 0 {
+            self.emit_u16(OpCode::PopN, scope.count)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_if(&mut self, statement: &If) -> CompileResult {
+        self.mark_line(statement.line());
+        self.compile_expression(statement.condition())?;
+
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit(OpCode::Pop);
+        self.compile_statement(statement.then_branch())?;
+
+        let end_jump = self.emit_jump(OpCode::Jump);
+        self.patch_jump(else_jump)?;
+        self.emit(OpCode::Pop);
+
+        if let Some(else_branch) = statement.else_branch() {
+            self.compile_statement(else_branch)?;
+        }
+
+        self.patch_jump(end_jump)
+    }
+
+    fn visit_while(&mut self, statement: &While) -> CompileResult {
+        self.mark_line(statement.line());
+        let start = self.chunk.code().len();
+        self.compile_expression(statement.condition())?;
+
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit(OpCode::Pop);
+        self.compile_statement(statement.body())?;
+        self.emit_loop(start)?;
+
+        self.patch_jump(exit_jump)?;
+        self.emit(OpCode::Pop);
+
+        Ok(())
+    }
+}
+
+fn compile_error(message: String) -> Error {
+    Error::new(
+        ErrorKind::CompileError { message }
+    )
+}
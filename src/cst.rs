@@ -0,0 +1,191 @@
+use super::{
+    dialect::Dialect,
+    token::{Token, Span},
+    ast::Statement,
+    parser::Parser,
+    error::Error
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    Program,
+    Var,
+    ExpressionStatement,
+    Print,
+    Block,
+    If,
+    While,
+    For,
+    Binary,
+    Logical,
+    Assign,
+    Ternary,
+    Unary,
+    MethodCall,
+    Call,
+    Grouping,
+    Literal,
+    Variable,
+    Interpolation
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    Node(Node),
+    Token(Token)
+}
+
+/// Concrete syntax tree built by `Parser::parse_tree`. Unlike the AST it
+/// keeps every token as written, including whitespace and comments when
+/// they were scanned, and `for` loops are not desugared. Trivia belongs to
+/// the innermost node open when it was scanned, so a node starts and ends
+/// with a significant token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    kind: NodeKind,
+    children: Vec<Element>
+}
+
+impl Node {
+    fn new(kind: NodeKind) -> Self {
+        Self {
+            kind,
+            children: Vec::new()
+        }
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    pub fn children(&self) -> &[Element] {
+        &self.children
+    }
+
+    /// Child nodes, without the tokens between them.
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter_map(|child| match child {
+            Element::Node(node) => Some(node),
+            Element::Token(_) => None
+        })
+    }
+
+    /// Every token in the tree, in source order.
+    pub fn tokens(&self) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens(&self, tokens: &mut Vec<Token>) {
+        for child in &self.children {
+            match child {
+                Element::Node(node) => node.collect_tokens(tokens),
+                Element::Token(token) => tokens.push(*token)
+            }
+        }
+    }
+
+    /// Bytes from the first to the last token; `None` for an empty program.
+    pub fn span(&self) -> Option<Span> {
+        let tokens = self.tokens();
+        let (first, last) = (tokens.first()?, tokens.last()?);
+
+        Some(Span::new(first.span().start(), last.span().end()))
+    }
+
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        self.span().map_or("", |span| &source[span.start()..span.end()])
+    }
+
+    /// The AST of a tree, as `Parser::parse` gives it for the same source.
+    pub fn statements(&self, source: &str, dialect: Dialect) -> Result<Vec<Statement>, Error> {
+        let mut tokens = self.tokens().into_iter().filter(|token| !token.token_type().is_trivia()).map(Ok);
+        let mut parser = Parser::with_dialect(source, &mut tokens, dialect);
+
+        // The tree was built within a depth limit already, and parsing it
+        // again nests no deeper.
+        parser.set_max_depth(usize::MAX);
+        parser.parse()
+    }
+}
+
+/// Position in the tree under construction, taken before parsing something
+/// that may later turn out to be the first child of a new node.
+#[derive(Debug, Copy, Clone)]
+pub(super) struct Checkpoint {
+    depth: usize,
+    children: usize
+}
+
+/// Assembles a `Node` from the parser's events. Trivia is held back until
+/// the next event, so that it ends up between nodes rather than at their
+/// edges.
+#[derive(Debug)]
+pub(super) struct Builder {
+    stack: Vec<Node>,
+    trivia: Vec<Token>
+}
+
+impl Builder {
+    pub(super) fn new() -> Self {
+        Self {
+            stack: vec![Node::new(NodeKind::Program)],
+            trivia: Vec::new()
+        }
+    }
+
+    pub(super) fn trivia(&mut self, token: Token) {
+        self.trivia.push(token);
+    }
+
+    pub(super) fn token(&mut self, token: Token) {
+        self.flush_trivia();
+        self.top().children.push(Element::Token(token));
+    }
+
+    pub(super) fn start(&mut self, kind: NodeKind) {
+        self.flush_trivia();
+        self.stack.push(Node::new(kind));
+    }
+
+    pub(super) fn checkpoint(&mut self) -> Checkpoint {
+        self.flush_trivia();
+
+        Checkpoint {
+            depth: self.stack.len(),
+            children: self.top().children.len()
+        }
+    }
+
+    /// Starts a node that takes in everything added since `checkpoint`.
+    pub(super) fn start_at(&mut self, checkpoint: Checkpoint, kind: NodeKind) {
+        assert_eq!(checkpoint.depth, self.stack.len(), "checkpoint from another node");
+
+        let mut node = Node::new(kind);
+        node.children = self.top().children.split_off(checkpoint.children);
+        self.stack.push(node);
+    }
+
+    pub(super) fn finish(&mut self) {
+        let node = self.stack.pop().expect("no node to finish");
+        self.top().children.push(Element::Node(node));
+    }
+
+    /// The root, once the parser is done.
+    pub(super) fn build(mut self) -> Node {
+        self.flush_trivia();
+        assert_eq!(self.stack.len(), 1, "unfinished nodes");
+
+        self.stack.pop().unwrap()
+    }
+
+    fn flush_trivia(&mut self) {
+        let trivia = std::mem::take(&mut self.trivia);
+        self.top().children.extend(trivia.into_iter().map(Element::Token));
+    }
+
+    fn top(&mut self) -> &mut Node {
+        self.stack.last_mut().expect("the program node is never finished")
+    }
+}
@@ -0,0 +1,76 @@
+use std::{fmt, str};
+
+/// Language profile selecting between reference Lox and this interpreter's extensions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// Current behavior: case-insensitive keywords, `null`, truthy coercions,
+    /// comma and ternary operators, interpolation, extended number literals
+    /// and built-in methods on strings.
+    #[default]
+    Extended,
+    /// Strict reference Lox: case-sensitive keywords, `nil`, only `false` and `nil`
+    /// are falsy and operators do not convert between numbers and strings.
+    Reference
+}
+
+impl Dialect {
+    pub fn case_insensitive_keywords(&self) -> bool {
+        matches!(self, Dialect::Extended)
+    }
+
+    pub fn null_keyword(&self) -> &'static str {
+        match self {
+            Dialect::Extended => "null",
+            Dialect::Reference => "nil"
+        }
+    }
+
+    pub fn falsy_zero_and_empty(&self) -> bool {
+        matches!(self, Dialect::Extended)
+    }
+
+    pub fn implicit_conversions(&self) -> bool {
+        matches!(self, Dialect::Extended)
+    }
+
+    pub fn comma_operator(&self) -> bool {
+        matches!(self, Dialect::Extended)
+    }
+
+    pub fn ternary_operator(&self) -> bool {
+        matches!(self, Dialect::Extended)
+    }
+
+    pub fn string_interpolation(&self) -> bool {
+        matches!(self, Dialect::Extended)
+    }
+
+    pub fn extended_number_literals(&self) -> bool {
+        matches!(self, Dialect::Extended)
+    }
+
+    pub fn builtin_methods(&self) -> bool {
+        matches!(self, Dialect::Extended)
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dialect::Extended => write!(f, "extended"),
+            Dialect::Reference => write!(f, "reference")
+        }
+    }
+}
+
+impl str::FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "extended" => Ok(Dialect::Extended),
+            "reference" => Ok(Dialect::Reference),
+            _ => Err(format!("unknown dialect \"{s}\", expected \"extended\" or \"reference\""))
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::fmt::Write;
+
+use super::chunk::{Chunk, OpCode};
+
+pub fn disassemble(chunk: &Chunk, name: &str) -> String {
+    let mut output = format!("== {name} ==\n");
+    let mut offset = 0;
+
+    while offset < chunk.code().len() {
+        offset = disassemble_instruction(chunk, offset, &mut output);
+    }
+
+    output
+}
+
+/// Appends one instruction to `output` and returns the offset of the next one.
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize, output: &mut String) -> usize {
+    let _ = write!(output, "{offset:04} ");
+
+    let line = chunk.line(offset);
+
+    if offset > 0 && line == chunk.line(offset - 1) {
+        output.push_str("   | ");
+    } else {
+        let _ = write!(output, "{line:4} ");
+    }
+
+    let byte = chunk.code()[offset];
+
+    let op_code = match OpCode::from_byte(byte) {
+        Some(op_code) => op_code,
+        None => {
+            let _ = writeln!(output, "Unknown opcode {byte}");
+            return offset + 1;
+        }
+    };
+
+    use OpCode::*;
+
+    match op_code {
+        Constant | DefineGlobal | GetGlobal | SetGlobal => {
+            let index = chunk.read_u16(offset + 1) as usize;
+            let _ = writeln!(output, "{:<16} {index:4} '{}'", op_code.to_string(), chunk.constant(index));
+        },
+        Jump | JumpIfFalse | JumpIfTrue => {
+            let jump = chunk.read_u16(offset + 1) as usize;
+            let _ = writeln!(output, "{:<16} {offset:4} -> {}", op_code.to_string(), offset + 3 + jump);
+        },
+        Loop => {
+            let jump = chunk.read_u16(offset + 1) as usize;
+            let _ = writeln!(output, "{:<16} {offset:4} -> {}", op_code.to_string(), offset + 3 - jump);
+        },
+        PopN | GetLocal | SetLocal | Interpolate => {
+            let operand = chunk.read_u16(offset + 1);
+            let _ = writeln!(output, "{:<16} {operand:4}", op_code.to_string());
+        },
+        Invoke | CallNative => {
+            let index = chunk.read_u16(offset + 1) as usize;
+            let count = chunk.code()[offset + 3];
+            let _ = writeln!(output, "{:<16} ({count} args) {index:4} '{}'", op_code.to_string(), chunk.constant(index));
+        },
+        _ => {
+            let _ = writeln!(output, "{op_code}");
+        }
+    }
+
+    offset + 1 + op_code.operand_size()
+}
@@ -1,68 +1,83 @@
-use std::collections;
+use std::{collections, rc::Rc};
 
 use super::{
     value::Value,
+    ast::Slot,
     error::{Error, ErrorKind}
 };
 
-#[derive(Debug)]
+/// Globals are looked up by name, locals live in per-block vectors and are
+/// addressed by the slots computed in `resolver`.
+#[derive(Debug, Default)]
 pub struct Environment {
-    enclosing: Option<Box<Environment>>,
-    values: collections::HashMap<String, Value>
+    globals: collections::HashMap<Rc<str>, Value>,
+    scopes: Vec<Vec<Value>>
 }
 
 impl Environment {
-    pub fn new(enclosing: Option<Box<Environment>>) -> Self {
-        Self {
-            values: collections::HashMap::new(),
-            enclosing,
-        }
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every value currently stored, globals and locals alike.
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.globals.values().chain(self.scopes.iter().flatten())
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
     }
 
-    pub fn define(&mut self, name: String, value: Value) {
-        self.values.insert(name, value);
+    pub fn define(&mut self, name: Rc<str>, value: Value) {
+        self.globals.insert(name, value);
     }
 
-    pub fn get(&self, name: &String) -> Result<&Value, Error> {
-        let value = self.values.get(name);
+    pub fn define_local(&mut self, index: usize, value: Value) {
+        let scope = self.scopes.last_mut().expect("local defined outside of a scope");
 
-        if value.is_none() && self.enclosing.is_some() {
-            return self.enclosing.as_ref().unwrap().get(name);
+        if index < scope.len() {
+            scope[index] = value;
+        } else {
+            scope.push(value);
         }
+    }
 
-        value.ok_or_else(|| {
-                Error::new(
-                    ErrorKind::RuntimeError {
-                        message: format!("undefined variable {}", name)
-                    }
-                )
-            })
+    pub fn get(&self, name: &str) -> Result<&Value, Error> {
+        self.globals.get(name).ok_or_else(|| undefined_variable(name))
     }
 
-    pub fn assign(&mut self, name: String, value: Value) -> Result<(), Error> {
-        match self.values.entry(name.clone()) {
-            entry @ collections::hash_map::Entry::Occupied(_) => {
-                entry.insert_entry(value);
-            },
-            collections::hash_map::Entry::Vacant(_) => {
-                if let Some(enclosing) = self.enclosing.as_mut() {
-                    return enclosing.assign(name, value);
-                }
+    pub fn get_local(&self, slot: Slot) -> &Value {
+        &self.scope(slot)[slot.index()]
+    }
 
-                return Err(
-                    Error::new(
-                        ErrorKind::RuntimeError {
-                            message: format!("undefined variable {}", name)
-                        }
-                    )
-                );
-            }
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), Error> {
+        match self.globals.get_mut(name) {
+            Some(entry) => {
+                *entry = value;
+                Ok(())
+            },
+            None => Err(undefined_variable(name))
         }
+    }
 
-        Ok(())
+    pub fn assign_local(&mut self, slot: Slot, value: Value) {
+        let depth = self.scopes.len() - 1 - slot.depth();
+        self.scopes[depth][slot.index()] = value;
     }
 
-    pub fn enclosing(self) -> Option<Box<Environment>> {
-        self.enclosing
+    fn scope(&self, slot: Slot) -> &[Value] {
+        &self.scopes[self.scopes.len() - 1 - slot.depth()]
     }
-}
\ No newline at end of file
+}
+
+fn undefined_variable(name: &str) -> Error {
+    Error::new(
+        ErrorKind::RuntimeError {
+            message: format!("undefined variable {}", name)
+        }
+    )
+}
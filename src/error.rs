@@ -1,31 +1,116 @@
 use std::{fmt, error};
 
-use super::token::Token;
+use super::{limits::Limit, token::Span};
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub(super) enum ErrorKind {
     ScannerError {
         line: usize,
+        span: Span,
         message: String
     },
     ParserError {
-        token: Option<Token>,
+        /// Text and line of the offending token, if there was one.
+        token: Option<(String, usize)>,
+        span: Option<Span>,
+        message: String
+    },
+    CompileError {
         message: String
     },
     RuntimeError {
         message: String
+    },
+    LimitError {
+        limit: Limit
+    },
+    /// Not a failure: the script called `exit`.
+    Exit {
+        code: i32
     }
 }
 
 #[derive(Debug)]
 pub struct Error {
-    kind: ErrorKind
+    kind: ErrorKind,
+    /// Span and line of the code a runtime error happened in, where the
+    /// backend tracks it.
+    location: Option<(Span, usize)>
 }
 
 impl Error {
     pub(super) fn new(kind: ErrorKind) -> Self {
         Self {
-            kind
+            kind,
+            location: None
+        }
+    }
+
+    /// Places a runtime error at `span` unless it already has a more
+    /// precise location.
+    pub(super) fn at(mut self, span: Span, line: usize) -> Self {
+        if matches!(self.kind, ErrorKind::RuntimeError { .. }) && self.location.is_none() {
+            self.location = Some((span, line));
+        }
+
+        self
+    }
+
+    /// Line of the source the error was found on. Errors found while
+    /// scanning or parsing know their line, runtime errors only inside
+    /// an interpolation.
+    pub fn line(&self) -> Option<usize> {
+        match &self.kind {
+            ErrorKind::ScannerError { line, .. } => Some(*line),
+            ErrorKind::ParserError { token: Some((_, line)), .. } => Some(*line),
+            _ => self.location.map(|(_, line)| line)
+        }
+    }
+
+    /// Bytes of the source the error points at. Like `line`, only known
+    /// for some errors.
+    pub fn span(&self) -> Option<Span> {
+        match &self.kind {
+            ErrorKind::ScannerError { span, .. } => Some(*span),
+            ErrorKind::ParserError { span, .. } => *span,
+            _ => self.location.map(|(span, _)| span)
+        }
+    }
+
+    /// Whether the error happened while the program was running, as
+    /// opposed to while it was scanned, parsed or compiled.
+    pub fn is_runtime(&self) -> bool {
+        matches!(self.kind, ErrorKind::RuntimeError { .. } | ErrorKind::LimitError { .. })
+    }
+
+    /// The description of the error, without the "Error:" prefix and the
+    /// location.
+    pub fn message(&self) -> String {
+        use ErrorKind::*;
+
+        match &self.kind {
+            ScannerError { message, .. } | ParserError { message, .. } => message.clone(),
+            CompileError { message } | RuntimeError { message } => message.clone(),
+            LimitError { limit } => limit.to_string(),
+            Exit { code } => format!("Exited with status {code}")
+        }
+    }
+
+    /// The status the script asked to exit with, if it called `exit`.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.kind {
+            ErrorKind::Exit { code } => Some(code),
+            _ => None
+        }
+    }
+
+    /// The execution limit that stopped the program, if that is what
+    /// this error reports.
+    pub fn limit(&self) -> Option<Limit> {
+        match self.kind {
+            ErrorKind::LimitError { limit } => Some(limit),
+            _ => None
         }
     }
 }
@@ -35,17 +120,29 @@ impl fmt::Display for Error {
         use ErrorKind::*;
 
         match &self.kind {
-            ScannerError { line, message } => {
+            ScannerError { line, message, .. } => {
                 write!(f, "Error: {message} in {line}")
             },
-            ParserError { token, message } => {
+            ParserError { token, message, .. } => {
                 match token {
-                    Some(token) => write!(f, "Error: {message} at \'{}\' in {}", token, token.line()),
+                    Some((lexeme, line)) => write!(f, "Error: {message} at \'{lexeme}\' in {line}"),
                     None => write!(f, "Error: {message}")
                 }
             },
             RuntimeError { message } => {
+                match self.location {
+                    Some((_, line)) => write!(f, "Error: {message} in {line}"),
+                    None => write!(f, "Error: {message}")
+                }
+            },
+            CompileError { message } => {
                 write!(f, "Error: {message}")
+            },
+            LimitError { limit } => {
+                write!(f, "Error: {limit}")
+            },
+            Exit { code } => {
+                write!(f, "Exited with status {code}")
             }
         }
     }
@@ -0,0 +1,387 @@
+use super::{
+    dialect::Dialect,
+    scanner::Scanner,
+    parser::{self, Parser},
+    unparser::Unparser,
+    token::{Token, TokenType},
+    cst::{Element, Node, NodeKind},
+    error::{Error, ErrorKind}
+};
+
+const INDENT: &str = "    ";
+
+/// How an element of a node is placed after the one before it. The first
+/// element of a node is placed by the node's parent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Layout {
+    Inherit,
+    Tight,
+    Space,
+    /// On a new line, keeping one blank line if the source had any.
+    Line,
+    /// On a new line, without blank lines.
+    Break,
+    /// The body of `if`, `while` or `for`: a block stays on the same line,
+    /// anything else goes on its own, indented line.
+    Body
+}
+
+/// Formats source code in one fixed style: four spaces of indentation,
+/// one statement per line, opening braces on the line of their statement,
+/// single spaces around binary operators and at most one blank line in a
+/// row. Comments are kept where they were, either at the end of a line of
+/// code or on a line of their own. Keywords are written in lowercase.
+#[derive(Debug)]
+pub struct Formatter {
+    dialect: Dialect,
+    max_depth: usize
+}
+
+impl Formatter {
+    pub fn new(dialect: Dialect) -> Self {
+        Self {
+            dialect,
+            max_depth: parser::MAX_DEPTH
+        }
+    }
+
+    /// Limits how deeply the source may nest, as `Parser::set_max_depth`
+    /// does.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// The formatted source, or the error that kept it from being parsed.
+    /// Fails rather than return code that parses to a different program.
+    pub fn format(&self, source: &str) -> Result<String, Error> {
+        let tree = self.parse_tree(source)?;
+        let mut writer = Writer::new(source);
+        writer.node(&tree);
+        writer.finish();
+
+        let formatted = writer.output;
+        let before = Unparser::new(self.dialect).unparse(&tree.statements(source, self.dialect)?)?;
+        let after = self.unparse(&formatted)
+            .map_err(|err| bug(format!("the formatted code does not parse: {err}")))?;
+
+        if before != after {
+            return Err(bug("formatting would change the program".into()));
+        }
+
+        Ok(formatted)
+    }
+
+    fn parse_tree(&self, source: &str) -> Result<Node, Error> {
+        let scanner = Scanner::new(source, self.dialect);
+        let mut tokens = scanner.tokens_with_trivia();
+        let mut parser = Parser::with_dialect(source, &mut tokens, self.dialect);
+        parser.set_max_depth(self.max_depth);
+
+        parser.parse_tree()
+    }
+
+    /// The program in a form that only depends on its syntax tree, so equal
+    /// for sources that only differ in layout.
+    fn unparse(&self, source: &str) -> Result<String, Error> {
+        let scanner = Scanner::new(source, self.dialect);
+        let mut tokens = scanner.tokens();
+        let mut parser = Parser::with_dialect(source, &mut tokens, self.dialect);
+        parser.set_max_depth(self.max_depth);
+        let statements = parser.parse()?;
+
+        Unparser::new(self.dialect).unparse(&statements)
+    }
+}
+
+struct Writer<'a> {
+    source: &'a str,
+    output: String,
+    indent: usize,
+    at_line_start: bool,
+    space: bool,
+    /// A line comment was written, so nothing more fits on the line.
+    line_ended: bool,
+    /// A line break here would split a statement, so the next line is a
+    /// continuation and indented one level more.
+    in_statement: bool,
+    /// Line breaks in the source since the last thing written.
+    newlines: usize,
+    /// Where the last token that is not a comment ends in the output.
+    code_end: usize
+}
+
+impl<'a> Writer<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            output: String::new(),
+            indent: 0,
+            at_line_start: true,
+            space: false,
+            line_ended: false,
+            in_statement: false,
+            newlines: 0,
+            code_end: 0
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        let kind = node.kind();
+        let empty_block = kind == NodeKind::Block
+            && node.children().iter().all(|child| matches!(child, Element::Token(token) if token.token_type() != TokenType::Comment));
+        let mut previous = None;
+
+        for child in node.children() {
+            if let Element::Token(token) = child {
+                if token.token_type().is_trivia() {
+                    self.trivia(token);
+                    continue;
+                }
+
+                if kind == NodeKind::Block && token.token_type() == TokenType::RightBrace {
+                    self.indent -= 1;
+                }
+            }
+
+            match layout(kind, previous, child, empty_block) {
+                Layout::Inherit => {},
+                Layout::Tight => self.space = false,
+                Layout::Space => self.space = true,
+                Layout::Line => self.statement_line(true),
+                Layout::Break => self.statement_line(false),
+                Layout::Body => match child {
+                    Element::Node(body) if body.kind() != NodeKind::Block => {
+                        self.indent += 1;
+                        self.statement_line(false);
+                        self.node(body);
+                        self.indent -= 1;
+                        previous = Some(child);
+                        continue;
+                    },
+                    _ => self.space = true
+                }
+            }
+
+            match child {
+                Element::Node(child) => self.node(child),
+                Element::Token(token) if is_terminator(kind, token) => self.terminator(token),
+                Element::Token(token) => self.token(token)
+            }
+
+            if kind == NodeKind::Block && matches!(child, Element::Token(token) if token.token_type() == TokenType::LeftBrace) {
+                self.indent += 1;
+                self.in_statement = false;
+            }
+
+            previous = Some(child);
+        }
+
+        if is_statement(kind) {
+            self.in_statement = false;
+        }
+    }
+
+    fn token(&mut self, token: &Token) {
+        let lexeme = token.lexeme(self.source);
+
+        if is_keyword(token.token_type()) {
+            self.word(&lexeme.to_lowercase());
+        } else {
+            self.word(lexeme);
+        }
+
+        self.code_end = self.output.len();
+    }
+
+    /// A trailing comment before a terminator goes after it rather than
+    /// push the terminator onto a line of its own.
+    fn terminator(&mut self, token: &Token) {
+        if self.line_ended && !self.output[self.code_end..].contains('\n') {
+            self.output.insert(self.code_end, ';');
+            self.code_end += 1;
+            self.newlines = 0;
+        } else {
+            self.token(token);
+        }
+    }
+
+    fn trivia(&mut self, token: &Token) {
+        let text = token.lexeme(self.source);
+
+        if token.token_type() == TokenType::Whitespace {
+            self.newlines += text.matches('\n').count();
+            return;
+        }
+
+        let in_statement = self.in_statement;
+
+        if self.newlines > 0 || self.output.is_empty() {
+            // A comment on a line of its own keeps the line to itself.
+            self.line(true);
+            self.word(text);
+            self.line_ended = true;
+        } else {
+            self.space = true;
+            self.word(text);
+            self.space = true;
+            self.line_ended = text.starts_with("//");
+        }
+
+        self.in_statement = in_statement;
+    }
+
+    fn word(&mut self, text: &str) {
+        if self.line_ended {
+            self.line(false);
+        }
+
+        if self.at_line_start {
+            let continuation = usize::from(self.in_statement);
+            self.output.push_str(&INDENT.repeat(self.indent + continuation));
+        } else if self.space {
+            self.output.push(' ');
+        }
+
+        self.output.push_str(text);
+        self.at_line_start = false;
+        self.space = false;
+        self.in_statement = true;
+        self.newlines = 0;
+    }
+
+    /// A line break that is part of the layout rather than forced by a
+    /// comment.
+    fn statement_line(&mut self, blank: bool) {
+        self.in_statement = false;
+        self.line(blank);
+    }
+
+    /// Ends the current line. Keeps a blank line from the source if
+    /// `blank` allows it, except at the start of a block or the file.
+    fn line(&mut self, blank: bool) {
+        if !self.at_line_start {
+            self.output.push('\n');
+            self.at_line_start = true;
+        }
+
+        if blank && self.newlines > 1 && !self.output.is_empty() && !self.output.ends_with("{\n") {
+            self.output.push('\n');
+        }
+
+        self.space = false;
+        self.line_ended = false;
+        self.newlines = 0;
+    }
+
+    fn finish(&mut self) {
+        self.newlines = 0;
+        self.line(false);
+    }
+}
+
+/// The source was valid, so a formatted result that is not is our fault
+/// rather than the user's.
+fn bug(message: String) -> Error {
+    Error::new(ErrorKind::CompileError { message: format!("Formatter bug: {message}") })
+}
+
+fn layout(kind: NodeKind, previous: Option<&Element>, child: &Element, empty_block: bool) -> Layout {
+    use TokenType::*;
+
+    let Some(previous) = previous else {
+        return match kind {
+            NodeKind::Program => Layout::Line,
+            _ => Layout::Inherit
+        };
+    };
+
+    let child_token = token_type(child);
+    let previous_token = token_type(previous);
+
+    match kind {
+        NodeKind::Program => Layout::Line,
+        NodeKind::Block => match child_token {
+            Some(RightBrace) if empty_block => Layout::Tight,
+            Some(RightBrace) => Layout::Break,
+            _ => Layout::Line
+        },
+        NodeKind::Var | NodeKind::Print | NodeKind::ExpressionStatement => match child_token {
+            Some(Semicolon) => Layout::Tight,
+            _ => Layout::Space
+        },
+        NodeKind::If => match (previous_token, child_token) {
+            (_, Some(LeftParen)) => Layout::Space,
+            (Some(RightParen), _) => Layout::Body,
+            (_, Some(Else)) if is_node(previous, NodeKind::Block) => Layout::Space,
+            (_, Some(Else)) => Layout::Break,
+            (Some(Else), _) if is_node(child, NodeKind::If) => Layout::Space,
+            (Some(Else), _) => Layout::Body,
+            _ => Layout::Tight
+        },
+        NodeKind::While => match (previous_token, child_token) {
+            (_, Some(LeftParen)) => Layout::Space,
+            (Some(RightParen), _) => Layout::Body,
+            _ => Layout::Tight
+        },
+        NodeKind::For => match (previous_token, child_token) {
+            (_, Some(LeftParen)) => Layout::Space,
+            (Some(RightParen), _) => Layout::Body,
+            (_, Some(Semicolon | RightParen)) => Layout::Tight,
+            // After the initializer or the condition.
+            (Some(Semicolon), _) => Layout::Space,
+            (None, _) if is_node(previous, NodeKind::Var) || is_node(previous, NodeKind::ExpressionStatement) => Layout::Space,
+            _ => Layout::Tight
+        },
+        NodeKind::Binary | NodeKind::Logical => match child_token {
+            Some(Comma) => Layout::Tight,
+            _ => Layout::Space
+        },
+        NodeKind::Assign | NodeKind::Ternary => Layout::Space,
+        NodeKind::MethodCall | NodeKind::Call => match (previous_token, child_token) {
+            (Some(Comma), _) => Layout::Space,
+            // "1." could scan as the start of a number.
+            (_, Some(Dot)) if last_token(previous) == Some(Number) => Layout::Space,
+            _ => Layout::Tight
+        },
+        NodeKind::Unary | NodeKind::Grouping | NodeKind::Literal | NodeKind::Variable | NodeKind::Interpolation => Layout::Tight
+    }
+}
+
+fn token_type(element: &Element) -> Option<TokenType> {
+    match element {
+        Element::Token(token) => Some(token.token_type()),
+        Element::Node(_) => None
+    }
+}
+
+fn last_token(element: &Element) -> Option<TokenType> {
+    match element {
+        Element::Token(token) => Some(token.token_type()),
+        Element::Node(node) => node.children().last().and_then(last_token)
+    }
+}
+
+fn is_node(element: &Element, kind: NodeKind) -> bool {
+    matches!(element, Element::Node(node) if node.kind() == kind)
+}
+
+fn is_statement(kind: NodeKind) -> bool {
+    use NodeKind::*;
+
+    matches!(kind, Var | ExpressionStatement | Print | Block | If | While | For)
+}
+
+fn is_terminator(kind: NodeKind, token: &Token) -> bool {
+    use NodeKind::*;
+
+    matches!(kind, Var | ExpressionStatement | Print) && token.token_type() == TokenType::Semicolon
+}
+
+fn is_keyword(token_type: TokenType) -> bool {
+    use TokenType::*;
+
+    matches!(
+        token_type,
+        And | Class | Else | False | Fun | For | If | Null | Or | Print | Return | Super | This | True | Var | While
+    )
+}
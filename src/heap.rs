@@ -0,0 +1,318 @@
+use std::{
+    cell::{Cell, Ref, RefCell},
+    collections::{HashMap, HashSet},
+    fmt,
+    mem,
+    rc::{Rc, Weak}
+};
+
+use super::{
+    value::Value,
+    error::Error,
+    limits::{self, Limit}
+};
+
+/// Values like lists are shared through `Rc`, which cannot free objects that
+/// refer to each other. The heap keeps a weak reference to every object it
+/// allocates so that a collection can find cycles that nothing outside the
+/// heap refers to and empty them, which lets reference counting free them.
+///
+/// Lists are immutable once built, so they cannot form cycles yet and
+/// reference counting alone frees them; the collector is here for the
+/// reference values that can point at each other, like maps and objects.
+///
+/// The roots are found rather than passed in: every reference held outside
+/// the heap, by an environment, the VM stack or a Rust temporary, keeps an
+/// object and what it refers to alive. So a collection is safe at any point.
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Weak<dyn Trace>>,
+    next_collection: usize,
+    stats: GcStats,
+    max_memory: Option<usize>,
+    live_bytes: usize,
+    allocated_bytes: usize
+}
+
+const MIN_COLLECTION_THRESHOLD: usize = 1024;
+
+impl Heap {
+    pub fn new() -> Self {
+        Self {
+            objects: Vec::new(),
+            next_collection: MIN_COLLECTION_THRESHOLD,
+            stats: GcStats::default(),
+            max_memory: None,
+            live_bytes: 0,
+            allocated_bytes: 0
+        }
+    }
+
+    pub fn alloc_list(&mut self, items: Vec<Value>) -> Value {
+        let list = Rc::new(List::new(items));
+        self.track(Rc::downgrade(&list) as Weak<dyn Trace>);
+
+        Value::List(list)
+    }
+
+    fn track(&mut self, object: Weak<dyn Trace>) {
+        self.objects.push(object);
+        self.stats.allocations += 1;
+    }
+
+    /// Whether enough objects were allocated since the last collection to
+    /// make another one worthwhile.
+    pub fn should_collect(&self) -> bool {
+        self.objects.len() >= self.next_collection
+    }
+
+    /// Marks every object referred to from outside the heap and everything
+    /// reachable from those, and sweeps the rest. Returns the number of
+    /// objects removed from the heap.
+    pub fn collect(&mut self) -> usize {
+        let before = self.objects.len();
+        let objects: Vec<Rc<dyn Trace>> = self.objects.iter().filter_map(Weak::upgrade).collect();
+
+        // Strong references from outside the heap: all of them, less the one
+        // `objects` holds and those from other objects in the heap.
+        let mut external: HashMap<*const (), usize> = objects.iter()
+            .map(|object| (address(object), Rc::strong_count(object) - 1))
+            .collect();
+
+        for object in &objects {
+            object.trace(&mut |item| {
+                if let Value::List(list) = item {
+                    if let Some(count) = external.get_mut(&(Rc::as_ptr(list) as *const ())) {
+                        *count -= 1;
+                    }
+                }
+            });
+        }
+
+        let mut gray: Vec<Rc<dyn Trace>> = objects.iter()
+            .filter(|object| external[&address(object)] > 0 && object.mark())
+            .cloned()
+            .collect();
+
+        while let Some(object) = gray.pop() {
+            object.trace(&mut |item| mark(item, &mut gray));
+        }
+
+        self.objects = objects.iter()
+            .filter(|object| {
+                if object.unmark() {
+                    return true;
+                }
+
+                object.release();
+                false
+            })
+            .map(Rc::downgrade)
+            .collect();
+
+        let freed = before - self.objects.len();
+
+        self.next_collection = (self.objects.len() * 2).max(MIN_COLLECTION_THRESHOLD);
+        self.stats.collections += 1;
+        self.stats.freed += freed;
+
+        freed
+    }
+
+    /// Approximate number of bytes script values may hold, see `Limits`.
+    pub fn set_max_memory(&mut self, max_memory: Option<usize>) {
+        self.max_memory = max_memory;
+    }
+
+    /// Fails if an allocation of `bytes` cannot fit next to the values that
+    /// were alive at the last measurement. Called before allocations whose
+    /// size is not bounded by existing values, like `repeat`.
+    pub fn reserve(&self, bytes: usize) -> Result<(), Error> {
+        match self.max_memory {
+            Some(max_memory) if self.live_bytes.saturating_add(bytes) > max_memory => {
+                Err(limits::limit_error(Limit::Memory(max_memory)))
+            },
+            _ => Ok(())
+        }
+    }
+
+    /// Accounts for a freshly created value. Returns `false` once the
+    /// allocations since the last measurement may exceed the memory limit;
+    /// the caller then has to `measure` its roots.
+    pub fn charge(&mut self, value: &Value) -> bool {
+        let Some(max_memory) = self.max_memory else {
+            return true;
+        };
+
+        self.allocated_bytes += owned_size(value, &mut HashSet::new());
+
+        self.live_bytes + self.allocated_bytes <= max_memory
+    }
+
+    /// Recomputes the memory held by everything reachable from `roots` and
+    /// fails if it exceeds the limit. Values shared through `Rc` are counted
+    /// once.
+    pub fn measure<'a>(&mut self, roots: impl IntoIterator<Item = &'a Value>) -> Result<(), Error> {
+        let mut seen = HashSet::new();
+
+        self.live_bytes = roots.into_iter()
+            .map(|root| mem::size_of::<Value>() + owned_size(root, &mut seen))
+            .sum();
+        self.allocated_bytes = 0;
+
+        self.reserve(0)
+    }
+
+    pub fn stats(&self) -> GcStats {
+        GcStats {
+            live: self.objects.iter().filter(|object| object.strong_count() > 0).count(),
+            ..self.stats
+        }
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn address(object: &Rc<dyn Trace>) -> *const () {
+    Rc::as_ptr(object) as *const ()
+}
+
+fn mark(value: &Value, gray: &mut Vec<Rc<dyn Trace>>) {
+    if let Value::List(list) = value {
+        if list.mark() {
+            gray.push(list.clone());
+        }
+    }
+}
+
+/// Bytes a value owns besides its own slot.
+fn owned_size(value: &Value, seen: &mut HashSet<*const ()>) -> usize {
+    match value {
+        Value::String(string) if seen.insert(Rc::as_ptr(string) as *const ()) => string.len(),
+        Value::List(list) if seen.insert(Rc::as_ptr(list) as *const ()) => {
+            let items = list.items();
+
+            mem::size_of::<List>() + items.iter()
+                .map(|item| mem::size_of::<Value>() + owned_size(item, seen))
+                .sum::<usize>()
+        },
+        _ => 0
+    }
+}
+
+/// Implemented by every object kind the heap tracks.
+trait Trace: fmt::Debug {
+    /// Sets the mark bit, returning `false` if it was already set.
+    fn mark(&self) -> bool;
+
+    /// Clears the mark bit, returning whether it was set.
+    fn unmark(&self) -> bool;
+
+    /// Calls `visit` with every value this object refers to.
+    fn trace(&self, visit: &mut dyn FnMut(&Value));
+
+    /// Drops the references this object holds.
+    fn release(&self);
+}
+
+/// Counters describing the heap's activity.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct GcStats {
+    allocations: usize,
+    collections: usize,
+    freed: usize,
+    live: usize
+}
+
+impl GcStats {
+    pub fn allocations(&self) -> usize {
+        self.allocations
+    }
+
+    pub fn collections(&self) -> usize {
+        self.collections
+    }
+
+    pub fn freed(&self) -> usize {
+        self.freed
+    }
+
+    pub fn live(&self) -> usize {
+        self.live
+    }
+}
+
+impl fmt::Display for GcStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "allocations: {}, collections: {}, freed: {}, live: {}",
+            self.allocations,
+            self.collections,
+            self.freed,
+            self.live
+        )
+    }
+}
+
+pub struct List {
+    marked: Cell<bool>,
+    items: RefCell<Vec<Value>>
+}
+
+impl List {
+    fn new(items: Vec<Value>) -> Self {
+        Self {
+            marked: Cell::new(false),
+            items: RefCell::new(items)
+        }
+    }
+
+    pub fn items(&self) -> Ref<'_, Vec<Value>> {
+        self.items.borrow()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.borrow().is_empty()
+    }
+}
+
+impl Trace for List {
+    fn mark(&self) -> bool {
+        !self.marked.replace(true)
+    }
+
+    fn unmark(&self) -> bool {
+        self.marked.replace(false)
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(&Value)) {
+        for item in self.items.borrow().iter() {
+            visit(item);
+        }
+    }
+
+    fn release(&self) {
+        self.items.borrow_mut().clear();
+    }
+}
+
+impl PartialEq for List {
+    fn eq(&self, other: &Self) -> bool {
+        *self.items.borrow() == *other.items.borrow()
+    }
+}
+
+impl fmt::Debug for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.items.borrow().iter()).finish()
+    }
+}
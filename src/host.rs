@@ -0,0 +1,95 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::{Duration, SystemTime, UNIX_EPOCH}
+};
+
+/// Virtual time advanced on every read in deterministic mode.
+const TICK: Duration = Duration::from_millis(1);
+
+/// Time and randomness as seen by scripts. Normally backed by the system
+/// clock and an entropy seeded generator; in deterministic mode the clock
+/// starts at zero and advances by a fixed tick on every read, and random
+/// numbers come from the given seed, so runs can be replayed exactly.
+#[derive(Debug, Clone)]
+pub struct Host {
+    seed: Option<u64>,
+    now: Option<Duration>,
+    random: SplitMix64
+}
+
+impl Host {
+    pub fn system() -> Self {
+        // Every `RandomState` is keyed with fresh entropy.
+        let seed = RandomState::new().build_hasher().finish();
+
+        Self {
+            seed: None,
+            now: None,
+            random: SplitMix64::new(seed)
+        }
+    }
+
+    pub fn deterministic(seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            now: Some(Duration::ZERO),
+            random: SplitMix64::new(seed)
+        }
+    }
+
+    /// The seed of a deterministic host.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.seed.is_some()
+    }
+
+    /// Time since the Unix epoch, or since the start of a deterministic run.
+    pub fn now(&mut self) -> Duration {
+        match &mut self.now {
+            Some(now) => {
+                *now += TICK;
+                *now
+            },
+            None => SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+        }
+    }
+
+    /// Uniformly distributed number in `[0, 1)`.
+    pub fn random(&mut self) -> f64 {
+        (self.random.next() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+impl Default for Host {
+    fn default() -> Self {
+        Self::system()
+    }
+}
+
+/// Small, fast generator; good enough for scripts, not for cryptography.
+#[derive(Debug, Clone)]
+struct SplitMix64 {
+    state: u64
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self {
+            state: seed
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        value ^ (value >> 31)
+    }
+}
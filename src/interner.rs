@@ -0,0 +1,33 @@
+use std::{collections, rc::Rc};
+
+/// Deduplicates identifiers and string literals so equal strings share one
+/// allocation and can be compared by pointer first.
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: collections::HashSet<Rc<str>>
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, string: &str) -> Rc<str> {
+        if let Some(interned) = self.strings.get(string) {
+            return interned.clone();
+        }
+
+        let interned: Rc<str> = Rc::from(string);
+        self.strings.insert(interned.clone());
+
+        interned
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
@@ -1,7 +1,10 @@
+use std::io;
+
 use super::{
+    dialect::Dialect,
     scanner::Scanner,
     error::{Error, ErrorKind},
-    parser::Parser,
+    parser::{self, Parser},
     value::Value,
     ast::{
         ExpressionVisitor,
@@ -21,377 +24,391 @@ use super::{
         Block,
         If,
         Logical,
-        While
+        While,
+        Interpolation,
+        MethodCall,
+        Call
     },
     token::TokenType,
-    environment::Environment
+    environment::Environment,
+    heap::{Heap, GcStats},
+    limits::{Budget, Limits, CancelHandle},
+    capabilities::Capabilities,
+    host::Host,
+    output::Output,
+    resolver::Resolver,
+    optimizer::Optimizer,
+    operations,
+    natives
 };
 
 #[derive(Debug)]
 pub struct Interpreter {
-    environment: Option<Box<Environment>>,
-    stack: Vec<Value>,
-    error: Option<Error>,
+    dialect: Dialect,
+    optimize: bool,
+    max_depth: usize,
+    depth: usize,
+    environment: Environment,
+    heap: Heap,
+    budget: Budget,
+    capabilities: Capabilities,
+    host: Host,
+    output: Output
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        Self::with_dialect(Dialect::default())
+    }
+
+    pub fn with_dialect(dialect: Dialect) -> Self {
         Self {
-            environment: Some(Box::new(Environment::new(None))),
-            stack: Vec::new(),
-            error: None
+            dialect,
+            optimize: false,
+            max_depth: parser::MAX_DEPTH,
+            depth: 0,
+            environment: Environment::new(),
+            heap: Heap::new(),
+            budget: Budget::default(),
+            capabilities: Capabilities::none(),
+            host: Host::system(),
+            output: Output::stdout()
         }
     }
 
+    /// Enables constant folding and dead branch pruning before execution.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    /// Limits how deeply programs may nest, both when parsing and when
+    /// evaluating. Defaults to `parser::MAX_DEPTH`.
+    ///
+    /// The limit does not size the stack: the calling thread needs enough
+    /// for it, up to 64 KiB per level in debug builds. The default fits a
+    /// standard 2 MiB thread; raise it only on a thread spawned with a
+    /// large enough stack.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
     pub fn run(&mut self, code: &str) -> Result<(), Error> {
-        let scanner = Scanner::from_str(code);
+        let scanner = Scanner::new(code, self.dialect);
         let mut tokens = scanner.tokens();
-        let mut parser = Parser::new(&mut tokens);
-        let statements = parser.parse()?;
+        let mut parser = Parser::with_dialect(code, &mut tokens, self.dialect);
+        parser.set_max_depth(self.max_depth);
+        let mut statements = parser.parse()?;
 
-        for statement in statements {
-            self.evaluate_statement(statement.as_ref())?;
+        if self.optimize {
+            statements = Optimizer::new(self.dialect).optimize(&statements);
+        }
+
+        let statements = Resolver::new().resolve(&statements);
+        self.budget.start();
+
+        for statement in statements.iter() {
+            self.evaluate_statement(statement)?;
         }
 
         Ok(())
     }
 
-    fn pop_from_stack(&mut self) -> Result<Value, Error> {
-        self.stack.pop().ok_or_else(|| {
-            Error::new(
-                ErrorKind::RuntimeError { message: "Expect value being in the stack".into() }
-            )
-        })
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.heap.set_max_memory(limits.max_memory());
+        self.budget.set_limits(limits);
     }
 
-    fn push_to_stack(&mut self, value: Value) {
-        self.stack.push(value);
+    pub fn limits(&self) -> Limits {
+        self.budget.limits()
     }
 
-    fn evaluate_statement(&mut self, statement: &dyn Statement) -> Result<(), Error> {
-        statement.accept(self);
-        self.handle_error()?;
-
-        Ok(())
+    /// Handle that stops the current or next run from another thread.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.budget.cancel_handle()
     }
 
-    fn evaluate_expression(&mut self, expression: &dyn Expression) -> Result<Value, Error> {
-        expression.accept(self);
-        self.handle_error()?;
-
-        self.pop_from_stack()
+    /// Host facilities scripts may use. None are granted by default.
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
     }
 
-    fn evaluate_binary(&mut self, expression: &Binary) -> Result<Value, Error> {
-        let left = self.evaluate_expression(expression.left())?;
-        let right = self.evaluate_expression(expression.right())?;
-        let operator = expression.operator();
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
 
-        use TokenType::*;
+    /// Makes time and randomness reproducible from `seed`, or goes back to
+    /// the system clock and entropy with `None`.
+    pub fn set_deterministic(&mut self, seed: Option<u64>) {
+        self.host = seed.map_or_else(Host::system, Host::deterministic);
+    }
 
-        let value = match operator.token_type() {
-            Minus => left.subtract(&right)?,
-            Slash => left.division(&right)?,
-            Star => left.mutiply(&right)?,
-            Plus => left.add(&right)?,
-            Greater => left.greater(&right)?,
-            GreaterEqual => left.greater_equal(&right)?,
-            Less => left.less(&right)?,
-            LessEqual => left.less_equal(&right)?,
-            EqualEqual => left.equal(&right)?,
-            BangEqual => left.not_equal(&right)?,
-            _ => unreachable!()
-        };
+    pub fn host(&mut self) -> &mut Host {
+        &mut self.host
+    }
 
-        Ok(value)
+    /// Sends what `print` writes to `output` instead of standard output.
+    pub fn set_output(&mut self, output: Box<dyn io::Write>) {
+        self.output = Output::new(output);
     }
 
-    fn evaluate_unary(&mut self, expression: &Unary) -> Result<Value, Error> {
-        let right = self.evaluate_expression(expression)?;
-        let operator = expression.operator();
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
 
-        use TokenType::*;
+    /// Runs a full garbage collection now and returns how many objects it
+    /// freed.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.heap.collect()
+    }
 
-        let value = match operator.token_type() {
-            Minus => {
-                match right.as_number()? {
-                    Value::Number(number) => Value::Number(-number),
-                    _ => unreachable!()
-                }
-            },
-            Bang => {
-                match right.as_boolean() {
-                    Value::True => Value::False,
-                    Value::False => Value::True,
-                    _ => unreachable!()
-                }
-            },
-            _ => unreachable!()
-        };
+    /// Checks a value created by an operation against the memory limit.
+    /// Temporaries held further up the evaluation are not counted.
+    fn allocate(&mut self, value: Value) -> Result<Value, Error> {
+        if !self.heap.charge(&value) {
+            self.heap.measure(self.environment.values().chain([&value]))?;
+        }
 
         Ok(value)
     }
 
-    fn evaluate_ternary(&mut self, expression: &Ternary) -> Result<Value, Error> {
-        let operator = expression.operator();
+    fn evaluate_statement(&mut self, statement: &Statement) -> Result<(), Error> {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
 
-        use TokenType::*;
+        self.enter()?;
+        let result = statement.accept(self);
+        self.depth -= 1;
 
-        let value = match operator.token_type() {
-            Query => {
-                let condition = self.evaluate_expression(expression.first())?;
+        result
+    }
 
-                if condition.as_boolean().is_true() {
-                    self.evaluate_expression(expression.second())?
-                } else {
-                    self.evaluate_expression(expression.third())?
-                }
-            },
-            _ => unreachable!()
-        };
+    fn evaluate_expression(&mut self, expression: &Expression) -> Result<Value, Error> {
+        self.enter()?;
+        let result = expression.accept(self);
+        self.depth -= 1;
 
-        Ok(value)
+        result
+    }
+
+    /// Evaluates the left operand of an operator chain. Like the parser,
+    /// this does not count as nesting: a long chain is a flat program.
+    fn evaluate_chained(&mut self, expression: &Expression) -> Result<Value, Error> {
+        self.budget.step()?;
+        expression.accept(self)
     }
 
-    fn handle_error(&mut self) -> Result<(), Error> {
-        if let Some(err) = self.error.take() {
-            Err(err)
-        } else {
-            Ok(())
+    fn enter(&mut self) -> Result<(), Error> {
+        self.budget.step()?;
+
+        if self.depth >= self.max_depth {
+            return Err(
+                Error::new(
+                    ErrorKind::RuntimeError {
+                        message: format!("Too deeply nested, the limit is {} levels", self.max_depth)
+                    }
+                )
+            );
         }
+
+        self.depth += 1;
+
+        Ok(())
     }
 }
 
-impl ExpressionVisitor for Interpreter {
-    fn visit_binary(&mut self, expression: &Binary) {
-        let result = self.evaluate_binary(expression);
-
-        match result {
-            Ok(value) => self.push_to_stack(value),
-            Err(error) => {
-                self.error = Some(error)
-            }
-        }
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    fn visit_grouping(&mut self, expression: &Grouping) {
-        let result = self.evaluate_expression(expression);
+impl ExpressionVisitor<Result<Value, Error>> for Interpreter {
+    fn visit_binary(&mut self, expression: &Binary) -> Result<Value, Error> {
+        let left = self.evaluate_chained(expression.left())?;
+        let right = self.evaluate_expression(expression.right())?;
 
-        match result {
-            Ok(value) => self.push_to_stack(value),
-            Err(error) => {
-                self.error = Some(error)
-            }
-        }
-    }
+        let value = operations::binary(self.dialect, expression.operator().token_type(), left, right)?;
 
-    fn visit_literal(&mut self, expression: &Literal) {
-        self.push_to_stack(expression.value().clone());
+        self.allocate(value)
     }
 
-    fn visit_unary(&mut self, expression: &Unary) {
-        let result = self.evaluate_unary(expression);
+    fn visit_grouping(&mut self, expression: &Grouping) -> Result<Value, Error> {
+        self.evaluate_expression(expression.expression())
+    }
 
-        match result {
-            Ok(value) => self.push_to_stack(value),
-            Err(error) => {
-                self.error = Some(error)
-            }
-        }
+    fn visit_literal(&mut self, expression: &Literal) -> Result<Value, Error> {
+        Ok(expression.value().clone())
     }
 
-    fn visit_ternary(&mut self, expression: &Ternary) {
-        let result = self.evaluate_ternary(expression);
+    fn visit_unary(&mut self, expression: &Unary) -> Result<Value, Error> {
+        let right = self.evaluate_expression(expression.right())?;
 
-        match result {
-            Ok(value) => self.push_to_stack(value),
-            Err(error) => {
-                self.error = Some(error)
-            }
+        match expression.operator().token_type() {
+            TokenType::Minus => operations::negate(self.dialect, right),
+            TokenType::Bang => Ok(Value::from(!operations::is_truthy(self.dialect, &right))),
+            _ => unreachable!()
         }
     }
-    
-    fn visit_variable(&mut self, expression: &Variable) {
-        let result = self.environment.as_ref().unwrap().get(expression.name());
 
-        match result {
-            Ok(value) => {
-                let value = value.clone();
-                self.push_to_stack(value)
+    fn visit_ternary(&mut self, expression: &Ternary) -> Result<Value, Error> {
+        let operator = expression.operator();
+
+        use TokenType::*;
+
+        let value = match operator.token_type() {
+            Query => {
+                let condition = self.evaluate_expression(expression.first())?;
+
+                if operations::is_truthy(self.dialect, &condition) {
+                    self.evaluate_expression(expression.second())?
+                } else {
+                    self.evaluate_expression(expression.third())?
+                }
             },
-            Err(error) => {
-                self.error = Some(error)
-            }
-        }
+            _ => unreachable!()
+        };
+
+        Ok(value)
     }
-    
-    fn visit_assign(&mut self, expression: &Assign) {
-        let result = self.evaluate_expression(expression.value());
 
-        let value = match result {
-            Ok(value) => value,
-            Err(error) => {
-                self.error = Some(error);
-                return;
-            }
+    fn visit_variable(&mut self, expression: &Variable) -> Result<Value, Error> {
+        let value = match expression.slot() {
+            Some(slot) => self.environment.get_local(slot),
+            None => self.environment.get(expression.name())?
         };
 
-        let result = self.environment.as_mut().unwrap().assign(expression.name().clone(), value.clone());
+        Ok(value.clone())
+    }
 
-        if let Err(error) = result {
-            self.error = Some(error)
+    fn visit_assign(&mut self, expression: &Assign) -> Result<Value, Error> {
+        let value = self.evaluate_expression(expression.value())?;
+        match expression.slot() {
+            Some(slot) => self.environment.assign_local(slot, value.clone()),
+            None => self.environment.assign(expression.name(), value.clone())?
         }
 
-        self.push_to_stack(value);
+        Ok(value)
     }
-    
-    fn visit_logical(&mut self, expression: &Logical) {
-        let result = self.evaluate_expression(expression.left());
-
-        let left = match result {
-            Ok(left) => left,
-            Err(error) => {
-                self.error = Some(error);
-                return
-            }
-        };
 
-        let left_as_bool = left.as_boolean();
+    fn visit_logical(&mut self, expression: &Logical) -> Result<Value, Error> {
+        let left = self.evaluate_chained(expression.left())?;
+        let left_is_truthy = operations::is_truthy(self.dialect, &left);
 
         match expression.operator().token_type() {
             TokenType::Or => {
-                if left_as_bool.is_true() {
-                    self.push_to_stack(left);
-                    return
+                if left_is_truthy {
+                    return Ok(left);
                 }
             },
             TokenType::And => {
-                if left_as_bool.is_false() {
-                    self.push_to_stack(left);
-                    return
+                if !left_is_truthy {
+                    return Ok(left);
                 }
             },
             _ => unreachable!()
         }
 
-        let result = self.evaluate_expression(expression.right());
+        self.evaluate_expression(expression.right())
+    }
 
-        match result {
-            Ok(value) => {
-                self.push_to_stack(value);
-            },
-            Err(error) => {
-                self.error = Some(error);
-            }
+    fn visit_interpolation(&mut self, expression: &Interpolation) -> Result<Value, Error> {
+        let mut parts = Vec::with_capacity(expression.parts().len());
+
+        for (part, (span, line)) in expression.parts().iter().zip(expression.locations()) {
+            parts.push(self.evaluate_expression(part).map_err(|err| err.at(*span, *line))?);
         }
+
+        self.allocate(operations::interpolate(&parts)?)
     }
-}
 
-impl StatementVisitor for Interpreter {
-    fn visit_expression_statement(&mut self, statement: &ExpressionStatement) {
-        let result = self.evaluate_expression(statement.expression());
+    fn visit_method_call(&mut self, expression: &MethodCall) -> Result<Value, Error> {
+        let object = self.evaluate_chained(expression.object())?;
+        let mut arguments = Vec::with_capacity(expression.arguments().len());
 
-        match result {
-            Ok(_value) => {},
-            Err(error) => {
-                self.error = Some(error)
-            }
+        for argument in expression.arguments() {
+            arguments.push(self.evaluate_expression(argument)?);
         }
+
+        let value = operations::invoke(self.dialect, &mut self.heap, &object, expression.name(), &arguments)?;
+
+        self.allocate(value)
     }
 
-    fn visit_print(&mut self, statement: &Print) {
-        let result = self.evaluate_expression(statement.expression());
+    fn visit_call(&mut self, expression: &Call) -> Result<Value, Error> {
+        let mut arguments = Vec::with_capacity(expression.arguments().len());
 
-        match result {
-            Ok(value) => println!("{}", value),
-            Err(error) => {
-                self.error = Some(error)
-            }
+        for argument in expression.arguments() {
+            arguments.push(self.evaluate_expression(argument)?);
         }
+
+        let value = natives::call_native(self.capabilities, &mut self.host, expression.name(), &arguments)?;
+
+        self.allocate(value)
     }
-    
-    fn visit_var(&mut self, statement: &Var) {
-        let result = self.evaluate_expression(statement.right());
+}
 
-        match result {
-            Ok(value) => {
-                self.environment.as_mut().unwrap().define(statement.name().clone(), value);
-            },
-            Err(error) => {
-                self.error = Some(error)
-            }
-        }
+impl StatementVisitor<Result<(), Error>> for Interpreter {
+    fn visit_expression_statement(&mut self, statement: &ExpressionStatement) -> Result<(), Error> {
+        self.evaluate_expression(statement.expression())?;
+
+        Ok(())
     }
-    
-    fn visit_block(&mut self, statement: &Block) {
-        let previous_env = self.environment.take().unwrap();
-        self.environment = Some(Box::new(Environment::new(Some(previous_env))));
 
-        let mut error = None;
+    fn visit_print(&mut self, statement: &Print) -> Result<(), Error> {
+        let value = self.evaluate_expression(statement.expression())?;
+        self.output.print(&operations::stringify(self.dialect, &value))?;
 
-        for statement in statement.statements() {
-            match self.evaluate_statement(statement.as_ref()) {
-                Ok(_) => {},
-                Err(err) => {
-                    error = Some(err);
-                    break;
-                }
-            }
+        Ok(())
+    }
+
+    fn visit_var(&mut self, statement: &Var) -> Result<(), Error> {
+        let value = self.evaluate_expression(statement.right())?;
+        match statement.slot() {
+            Some(index) => self.environment.define_local(index, value),
+            None => self.environment.define(statement.name().clone(), value)
         }
 
-        self.environment = self.environment.take().unwrap().enclosing();
-        self.error = error;
+        Ok(())
     }
-    
-    fn visit_if(&mut self, statement: &If) {
-        let result = self.evaluate_expression(statement.condition());
 
-        let condition = match result {
-            Ok(value) => value,
-            Err(error) => {
-                self.error = Some(error);
-                return
-            }
-        };
+    fn visit_block(&mut self, statement: &Block) -> Result<(), Error> {
+        self.environment.push_scope();
 
-        if condition.as_boolean().is_true() {
-            let result = self.evaluate_statement(statement.then_branch());
+        let result = statement.statements()
+            .iter()
+            .try_for_each(|statement| self.evaluate_statement(statement));
 
-            if let Err(error) = result {
-                self.error = Some(error);
-                return
-            }
-        } else if let Some(else_branch) = statement.else_branch() {
-            let result = self.evaluate_statement(else_branch);
+        self.environment.pop_scope();
 
-            if let Err(error) = result {
-                self.error = Some(error);
-            }
+        result
+    }
+
+    fn visit_if(&mut self, statement: &If) -> Result<(), Error> {
+        let condition = self.evaluate_expression(statement.condition())?;
+        let condition = operations::is_truthy(self.dialect, &condition);
+
+        if condition {
+            self.evaluate_statement(statement.then_branch())?;
+        } else if let Some(else_branch) = statement.else_branch() {
+            self.evaluate_statement(else_branch)?;
         }
+
+        Ok(())
     }
-    
-    fn visit_while(&mut self, statement: &While) {
-        loop {
-            let result = self.evaluate_expression(statement.condition());
 
-            let condition = match result {
-                Ok(value) => value,
-                Err(error) => {
-                    self.error = Some(error);
-                    return
-                }
-            };
+    fn visit_while(&mut self, statement: &While) -> Result<(), Error> {
+        loop {
+            let condition = self.evaluate_expression(statement.condition())?;
+            let condition = operations::is_truthy(self.dialect, &condition);
 
-            if condition.as_boolean().is_false() {
+            if !condition {
                 break;
             }
 
-            let result = self.evaluate_statement(statement.body());
-
-            if let Err(error) = result {
-                self.error = Some(error);
-                return
-            }
+            self.evaluate_statement(statement.body())?;
         }
+
+        Ok(())
     }
 }
@@ -7,6 +7,34 @@ pub mod parser;
 pub mod value;
 pub mod utils;
 pub mod environment;
+pub mod dialect;
+pub mod methods;
+pub mod natives;
+pub mod resolver;
+pub mod interner;
+pub mod operations;
+pub mod chunk;
+pub mod compiler;
+pub mod disassembler;
+pub mod vm;
+pub mod backend;
+pub mod optimizer;
+pub mod bench;
+pub mod heap;
+pub mod limits;
+pub mod capabilities;
+pub mod host;
+pub mod output;
+pub mod unparser;
+pub mod cst;
+pub mod formatter;
+pub mod lsp;
 
 pub use interpreter::Interpreter;
 pub use scanner::Scanner;
+pub use dialect::Dialect;
+pub use vm::Vm;
+pub use backend::{Backend, Executor};
+pub use limits::{Limits, CancelHandle};
+pub use capabilities::{Capabilities, Capability};
+pub use host::Host;
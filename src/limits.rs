@@ -0,0 +1,183 @@
+use std::{
+    fmt,
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    time::{Duration, Instant}
+};
+
+use super::error::{Error, ErrorKind};
+
+/// How often, in steps, the clock and the cancellation flag are checked.
+const CHECK_INTERVAL: u64 = 1024;
+
+/// Execution limits applied to every `run`. All of them are off by default.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Limits {
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+    max_memory: Option<usize>
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum number of evaluated statements and expressions (tree-walker)
+    /// or executed instructions (VM) per run.
+    pub fn set_max_steps(&mut self, max_steps: Option<u64>) {
+        self.max_steps = max_steps;
+    }
+
+    /// Wall-clock time a single run may take.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Approximate number of bytes held by strings, lists and variables.
+    /// Unlike the other limits it applies to everything the executor keeps,
+    /// including globals left over from earlier runs.
+    pub fn set_max_memory(&mut self, max_memory: Option<usize>) {
+        self.max_memory = max_memory;
+    }
+
+    pub fn max_steps(&self) -> Option<u64> {
+        self.max_steps
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn max_memory(&self) -> Option<usize> {
+        self.max_memory
+    }
+}
+
+/// The limit a run was stopped by.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Limit {
+    Steps(u64),
+    Timeout(Duration),
+    Memory(usize),
+    Cancelled
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Steps(steps) => write!(f, "Execution exceeded the limit of {steps} steps"),
+            Limit::Timeout(timeout) => write!(f, "Execution exceeded the timeout of {}ms", timeout.as_millis()),
+            Limit::Memory(bytes) => write!(f, "Execution exceeded the memory limit of {bytes} bytes"),
+            Limit::Cancelled => write!(f, "Execution was cancelled")
+        }
+    }
+}
+
+/// Stops a running program from another thread. The run it interrupts
+/// fails with `Limit::Cancelled`; later runs are not affected.
+#[derive(Debug, Default, Clone)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>
+}
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Withdraws a cancellation that no run has picked up yet.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> bool {
+        self.cancelled.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Tracks one run against its `Limits`.
+#[derive(Debug, Default)]
+pub(super) struct Budget {
+    limits: Limits,
+    cancel: CancelHandle,
+    steps: u64,
+    next_check: u64,
+    deadline: Option<Instant>
+}
+
+impl Budget {
+    pub(super) fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub(super) fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub(super) fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    pub(super) fn start(&mut self) {
+        self.steps = 0;
+        self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+        self.schedule_check();
+    }
+
+    #[inline]
+    pub(super) fn step(&mut self) -> Result<(), Error> {
+        self.steps += 1;
+
+        if self.steps >= self.next_check {
+            return self.check();
+        }
+
+        Ok(())
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn check(&mut self) -> Result<(), Error> {
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Err(limit_error(Limit::Steps(max_steps)));
+            }
+        }
+
+        if self.cancel.take() {
+            return Err(limit_error(Limit::Cancelled));
+        }
+
+        if let (Some(deadline), Some(timeout)) = (self.deadline, self.limits.timeout) {
+            if Instant::now() >= deadline {
+                return Err(limit_error(Limit::Timeout(timeout)));
+            }
+        }
+
+        self.schedule_check();
+
+        Ok(())
+    }
+
+    fn schedule_check(&mut self) {
+        self.next_check = self.steps + CHECK_INTERVAL;
+
+        if let Some(max_steps) = self.limits.max_steps {
+            self.next_check = self.next_check.min(max_steps.saturating_add(1));
+        }
+    }
+}
+
+pub(super) fn limit_error(limit: Limit) -> Error {
+    Error::new(
+        ErrorKind::LimitError { limit }
+    )
+}
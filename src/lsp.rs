@@ -0,0 +1,497 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write}
+};
+
+use serde_json::{json, Value};
+
+use super::{
+    dialect::Dialect,
+    scanner::Scanner,
+    parser::{self, Parser},
+    token::{Token, Span, TokenType},
+    cst::{Element, Node, NodeKind},
+    error::Error
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_PARAMS: i64 = -32602;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Largest message body the server reads, so that a bad `Content-Length`
+/// cannot make it allocate without bound.
+const MAX_MESSAGE_LENGTH: usize = 64 * 1024 * 1024;
+
+/// `SymbolKind.Variable` in the protocol.
+const VARIABLE_SYMBOL: u32 = 13;
+/// `DiagnosticSeverity.Error` in the protocol.
+const ERROR_SEVERITY: u32 = 1;
+
+type RequestError = (i64, String);
+
+/// Language server for scripts, speaking the Language Server Protocol over
+/// any reader and writer. Documents are synchronized in full on every
+/// change and checked with the scanner and parser; navigation needs a
+/// document without errors.
+#[derive(Debug)]
+pub struct Server {
+    dialect: Dialect,
+    max_depth: usize,
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+    exited: bool
+}
+
+impl Server {
+    pub fn new(dialect: Dialect) -> Self {
+        Self {
+            dialect,
+            max_depth: parser::MAX_DEPTH,
+            documents: HashMap::new(),
+            shut_down: false,
+            exited: false
+        }
+    }
+
+    /// Limits how deeply documents may nest, as `Parser::set_max_depth`
+    /// does.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Whether the client asked the server to shut down before it exited,
+    /// as it should.
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down
+    }
+
+    /// Serves messages from `reader` until the client sends "exit" or
+    /// closes the stream.
+    pub fn run<R: BufRead, W: Write>(&mut self, mut reader: R, mut writer: W) -> io::Result<()> {
+        while !self.exited {
+            let Some(body) = read_message(&mut reader)? else {
+                break;
+            };
+
+            let replies = match serde_json::from_str::<Value>(&body) {
+                Ok(message) => self.handle(&message),
+                Err(err) => vec![error_response(&Value::Null, (PARSE_ERROR, err.to_string()))]
+            };
+
+            for reply in replies {
+                write_message(&mut writer, &reply)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The responses and notifications to send for one incoming message.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let params = &message["params"];
+
+        match (message["method"].as_str(), message.get("id")) {
+            (Some(method), Some(id)) => {
+                let response = match self.request(method, params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err(err) => error_response(id, err)
+                };
+
+                vec![response]
+            },
+            (Some(method), None) => self.notification(method, params),
+            // Responses to requests the server never makes.
+            _ => Vec::new()
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, RequestError> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "documentSymbolProvider": true,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true
+                },
+                "serverInfo": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION")
+                }
+            })),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            },
+            "textDocument/documentSymbol" => {
+                let (_, document) = self.document(params)?;
+                let Some(symbols) = &document.symbols else {
+                    return Ok(json!([]));
+                };
+
+                let symbols: Vec<Value> = symbols.declarations.iter()
+                    .filter(|declaration| declaration.global)
+                    .map(|declaration| json!({
+                        "name": declaration.name,
+                        "kind": VARIABLE_SYMBOL,
+                        "range": document.range(declaration.statement),
+                        "selectionRange": document.range(declaration.span)
+                    }))
+                    .collect();
+
+                Ok(Value::from(symbols))
+            },
+            "textDocument/definition" => {
+                let (uri, document) = self.document(params)?;
+
+                Ok(match document.declaration_at(&params["position"]) {
+                    Some((declaration, _)) => document.location(uri, declaration.span),
+                    None => Value::Null
+                })
+            },
+            "textDocument/references" => {
+                let (uri, document) = self.document(params)?;
+                let (Some((declaration, index)), Some(symbols)) = (document.declaration_at(&params["position"]), &document.symbols) else {
+                    return Ok(Value::Null);
+                };
+
+                let mut spans = Vec::new();
+
+                if params["context"]["includeDeclaration"].as_bool().unwrap_or(false) {
+                    spans.push(declaration.span);
+                }
+
+                spans.extend(symbols.references.iter().filter(|(_, declaration)| *declaration == index).map(|(span, _)| *span));
+
+                Ok(spans.into_iter().map(|span| document.location(uri, span)).collect())
+            },
+            "textDocument/hover" => {
+                let (_, document) = self.document(params)?;
+
+                Ok(match document.declaration_at(&params["position"]) {
+                    Some((declaration, _)) => json!({
+                        "contents": {
+                            "kind": "markdown",
+                            "value": document.describe(declaration)
+                        }
+                    }),
+                    None => Value::Null
+                })
+            },
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method \"{method}\"")))
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let document = &params["textDocument"];
+        let uri = document["uri"].as_str().unwrap_or_default().to_string();
+
+        let text = match method {
+            "textDocument/didOpen" => document["text"].as_str(),
+            // Full synchronization: the last change holds the whole text.
+            "textDocument/didChange" => params["contentChanges"].as_array().and_then(|changes| changes.last()).and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![diagnostics_notification(&uri, document, Vec::new())];
+            },
+            "exit" => {
+                self.exited = true;
+                return Vec::new();
+            },
+            _ => return Vec::new()
+        };
+
+        let Some(text) = text else {
+            return Vec::new();
+        };
+
+        let new_document = Document::new(text.to_string(), self.dialect, self.max_depth);
+        let diagnostics = new_document.diagnostics();
+        self.documents.insert(uri.clone(), new_document);
+
+        vec![diagnostics_notification(&uri, document, diagnostics)]
+    }
+
+    fn document<'a>(&'a self, params: &'a Value) -> Result<(&'a str, &'a Document), RequestError> {
+        let uri = params["textDocument"]["uri"].as_str()
+            .ok_or_else(|| (INVALID_PARAMS, "Missing \"textDocument.uri\"".to_string()))?;
+
+        let document = self.documents.get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document \"{uri}\"")))?;
+
+        Ok((uri, document))
+    }
+}
+
+#[derive(Debug)]
+struct Document {
+    text: String,
+    /// Only known while the text parses.
+    symbols: Option<Symbols>,
+    error: Option<Error>
+}
+
+impl Document {
+    fn new(text: String, dialect: Dialect, max_depth: usize) -> Self {
+        let scanner = Scanner::new(&text, dialect);
+        let mut tokens = scanner.tokens_with_trivia();
+        let mut parser = Parser::with_dialect(&text, &mut tokens, dialect);
+        parser.set_max_depth(max_depth);
+        let result = parser.parse_tree();
+
+        let (symbols, error) = match result {
+            Ok(tree) => (Some(Symbols::collect(&tree, &text)), None),
+            Err(err) => (None, Some(err))
+        };
+
+        Self {
+            text,
+            symbols,
+            error
+        }
+    }
+
+    fn diagnostics(&self) -> Vec<Value> {
+        let Some(err) = &self.error else {
+            return Vec::new();
+        };
+
+        let range = match (err.span(), err.line()) {
+            (Some(span), _) => self.range(span),
+            // The whole line.
+            (None, Some(line)) => json!({
+                "start": { "line": line - 1, "character": 0 },
+                "end": { "line": line, "character": 0 }
+            }),
+            (None, None) => self.range(Span::new(self.text.len(), self.text.len()))
+        };
+
+        vec![json!({
+            "range": range,
+            "severity": ERROR_SEVERITY,
+            "source": env!("CARGO_PKG_NAME"),
+            "message": err.message()
+        })]
+    }
+
+    /// The declaration of the variable at `position`, and its index.
+    fn declaration_at(&self, position: &Value) -> Option<(&Declaration, usize)> {
+        let symbols = self.symbols.as_ref()?;
+        let offset = offset(&self.text, position)?;
+        let contains = |span: &Span| span.start() <= offset && offset <= span.end();
+
+        let index = symbols.references.iter()
+            .find(|(span, _)| contains(span))
+            .map(|(_, declaration)| *declaration)
+            .or_else(|| symbols.declarations.iter().position(|declaration| contains(&declaration.span)))?;
+
+        Some((&symbols.declarations[index], index))
+    }
+
+    fn describe(&self, declaration: &Declaration) -> String {
+        let scope = if declaration.global { "Global" } else { "Local" };
+        let line = self.text[..declaration.span.start()].matches('\n').count() + 1;
+        let code = self.text[declaration.statement.start()..declaration.statement.end()].lines().next().unwrap_or_default();
+
+        format!("```lox\n{code}\n```\n{scope} variable declared on line {line}")
+    }
+
+    fn range(&self, span: Span) -> Value {
+        json!({ "start": position(&self.text, span.start()), "end": position(&self.text, span.end()) })
+    }
+
+    fn location(&self, uri: &str, span: Span) -> Value {
+        json!({ "uri": uri, "range": self.range(span) })
+    }
+}
+
+#[derive(Debug)]
+struct Declaration {
+    name: String,
+    /// The name in the declaration.
+    span: Span,
+    /// The whole `var` statement.
+    statement: Span,
+    global: bool
+}
+
+/// The variables of a document and what each use of a name refers to,
+/// following the scoping rules of the resolver: blocks and `for` loops
+/// open a scope, and a declaration is visible after its statement.
+#[derive(Debug, Default)]
+struct Symbols {
+    declarations: Vec<Declaration>,
+    /// Spans of the names in expressions, with the declaration they use.
+    references: Vec<(Span, usize)>
+}
+
+impl Symbols {
+    fn collect(tree: &Node, source: &str) -> Self {
+        let mut symbols = Symbols::default();
+        let mut scopes = vec![Vec::new()];
+        symbols.node(tree, source, &mut scopes);
+        symbols
+    }
+
+    fn node(&mut self, node: &Node, source: &str, scopes: &mut Vec<Vec<usize>>) {
+        match node.kind() {
+            NodeKind::Block | NodeKind::For => {
+                scopes.push(Vec::new());
+                self.children(node, source, scopes);
+                scopes.pop();
+            },
+            NodeKind::Var => {
+                self.children(node, source, scopes);
+
+                let Some(name) = identifiers(node).next() else {
+                    return;
+                };
+
+                self.declarations.push(Declaration {
+                    name: name.lexeme(source).to_string(),
+                    span: name.span(),
+                    statement: node.span().unwrap_or(name.span()),
+                    global: scopes.len() == 1
+                });
+                scopes.last_mut().unwrap().push(self.declarations.len() - 1);
+            },
+            NodeKind::Variable => {
+                let Some(name) = identifiers(node).next() else {
+                    return;
+                };
+
+                let lexeme = name.lexeme(source);
+                let declaration = scopes.iter()
+                    .rev()
+                    .find_map(|scope| scope.iter().rev().find(|index| self.declarations[**index].name == lexeme));
+
+                if let Some(declaration) = declaration {
+                    self.references.push((name.span(), *declaration));
+                }
+            },
+            NodeKind::Call => {
+                // The name before the arguments is a native function, not
+                // a variable.
+                for child in node.nodes().skip(1) {
+                    self.node(child, source, scopes);
+                }
+            },
+            _ => self.children(node, source, scopes)
+        }
+    }
+
+    fn children(&mut self, node: &Node, source: &str, scopes: &mut Vec<Vec<usize>>) {
+        for child in node.nodes() {
+            self.node(child, source, scopes);
+        }
+    }
+}
+
+fn identifiers(node: &Node) -> impl Iterator<Item = &Token> {
+    node.children().iter().filter_map(|child| match child {
+        Element::Token(token) if token.token_type() == TokenType::Identifier => Some(token),
+        _ => None
+    })
+}
+
+/// Line and UTF-16 column of a byte offset, as the protocol counts them.
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count()
+    })
+}
+
+/// The byte offset of a protocol position, if it is inside `text`.
+fn offset(text: &str, position: &Value) -> Option<usize> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+
+    let line_start = match line {
+        0 => 0,
+        _ => text.match_indices('\n').nth(line - 1)?.0 + 1
+    };
+
+    let mut units = 0;
+
+    for (index, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + index);
+        }
+
+        units += c.len_utf16();
+    }
+
+    Some(text.len())
+}
+
+fn diagnostics_notification(uri: &str, document: &Value, diagnostics: Vec<Value>) -> Value {
+    let mut params = json!({ "uri": uri, "diagnostics": diagnostics });
+
+    if let Some(version) = document.get("version") {
+        params["version"] = version.clone();
+    }
+
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": params
+    })
+}
+
+fn error_response(id: &Value, (code, message): RequestError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message }
+    })
+}
+
+/// Reads the body of the next message, or `None` at the end of the stream.
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+    let mut header = String::new();
+
+    loop {
+        header.clear();
+
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            let value = value.trim().parse().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            length = Some(value);
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Message without a Content-Length header"));
+    };
+
+    if length > MAX_MESSAGE_LENGTH {
+        let message = format!("Message of {length} bytes is longer than the limit of {MAX_MESSAGE_LENGTH}");
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    String::from_utf8(body).map(Some).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
@@ -1,51 +1,541 @@
 use std::{
-    io::{self, Write, BufRead},
+    io::{self, Read, Write, BufRead},
     fs,
-    error
+    path::{Path, PathBuf},
+    time::Duration,
+    process,
+    thread,
+    error,
+    sync::{atomic::{AtomicBool, Ordering}, Arc}
 };
 
-use rust_tree_walk::Interpreter;
+use rust_tree_walk::{
+    Vm,
+    Dialect,
+    Backend,
+    Executor,
+    Limits,
+    limits::Limit,
+    Capabilities,
+    Capability,
+    Scanner,
+    disassembler,
+    parser::Parser,
+    optimizer::Optimizer,
+    formatter::Formatter,
+    lsp,
+    ast::Printer,
+    bench::{self, Summary}
+};
 
 type Error = Box<dyn error::Error>;
 
+/// Stack reserved for each level of nesting allowed by `--max-depth`; the
+/// parser and the tree-walker recurse once or more per level, and operator
+/// chains add up to `parser::LINKS_PER_LEVEL` levels of tree each.
+const STACK_PER_LEVEL: usize = 64 * 1024;
+/// Scripts run on a thread sized for their depth, so the command line
+/// allows more nesting than the library does by default.
+const DEFAULT_MAX_DEPTH: usize = 256;
+const MIN_STACK: usize = 8 * 1024 * 1024;
+const MAX_STACK: usize = 1024 * 1024 * 1024;
+
+#[derive(Debug, Copy, Clone)]
+struct Options {
+    dialect: Dialect,
+    backend: Backend,
+    optimize: bool,
+    max_depth: usize,
+    limits: Limits,
+    capabilities: Capabilities,
+    seed: Option<u64>
+}
+
+impl Options {
+    fn executor(&self) -> Box<dyn Executor> {
+        let mut executor = self.backend.executor(self.dialect);
+        executor.set_optimize(self.optimize);
+        executor.set_max_depth(self.max_depth);
+        executor.set_limits(self.limits);
+        executor.set_capabilities(self.capabilities);
+        executor.set_deterministic(self.seed);
+        executor
+    }
+}
+
 fn args() -> clap::ArgMatches {
     clap::Command::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
+        .args_conflicts_with_subcommands(true)
         .arg(clap::Arg::new("file"))
+        .arg(
+            clap::Arg::new("dialect")
+                .long("dialect")
+                .help("Language profile: \"extended\" or \"reference\" (strict Lox)")
+                .value_parser(clap::value_parser!(Dialect))
+                .default_value("extended")
+                .global(true)
+        )
+        .arg(
+            clap::Arg::new("backend")
+                .long("backend")
+                .help("Execution backend: \"tree\" (tree-walking) or \"bytecode\" (stack VM)")
+                .value_parser(clap::value_parser!(Backend))
+                .default_value("tree")
+                .global(true)
+        )
+        .arg(
+            clap::Arg::new("disassemble")
+                .long("disassemble")
+                .help("Print the compiled bytecode instead of running the program")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            clap::Arg::new("optimize")
+                .long("optimize")
+                .help("Fold constant expressions and prune dead branches before running")
+                .action(clap::ArgAction::SetTrue)
+                .global(true)
+        )
+        .arg(
+            clap::Arg::new("max-steps")
+                .long("max-steps")
+                .help("Abort a run after this many evaluation steps")
+                .value_parser(clap::value_parser!(u64))
+                .global(true)
+        )
+        .arg(
+            clap::Arg::new("timeout")
+                .long("timeout")
+                .value_name("MILLISECONDS")
+                .help("Abort a run that takes longer than this")
+                .value_parser(clap::value_parser!(u64))
+                .global(true)
+        )
+        .arg(
+            clap::Arg::new("max-memory")
+                .long("max-memory")
+                .value_name("BYTES")
+                .help("Abort a run once script values hold more than this many bytes")
+                .value_parser(clap::value_parser!(usize))
+                .global(true)
+        )
+        .arg(
+            clap::Arg::new("allow")
+                .long("allow")
+                .value_name("CAPABILITY")
+                .help("Let scripts use a host facility: \"files\", \"env\", \"clock\", \"exit\" or \"stdin\". May be repeated")
+                .value_parser(clap::value_parser!(Capability))
+                .action(clap::ArgAction::Append)
+                .global(true)
+        )
+        .arg(
+            clap::Arg::new("allow-all")
+                .long("allow-all")
+                .help("Grant every capability")
+                .action(clap::ArgAction::SetTrue)
+                .global(true)
+        )
+        .arg(
+            clap::Arg::new("seed")
+                .long("seed")
+                .help("Run deterministically: virtual clock and random numbers seeded with this value")
+                .value_parser(clap::value_parser!(u64))
+                .global(true)
+        )
+        .arg(
+            clap::Arg::new("gc-stats")
+                .long("gc-stats")
+                .help("Print garbage collector statistics to stderr after running a file")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            clap::Arg::new("max-depth")
+                .long("max-depth")
+                .help("Maximum nesting depth of statements and expressions [default: 256]")
+                .value_parser(clap::value_parser!(u64).range(1..=(MAX_STACK / STACK_PER_LEVEL) as u64))
+                .global(true)
+        )
+        .arg(
+            clap::Arg::new("print-ast")
+                .long("print-ast")
+                .help("Print the syntax tree (optimized with --optimize) instead of running the program")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .subcommand(
+            clap::Command::new("bench")
+                .about("Run benchmark scripts several times and report min, median and max wall time")
+                .arg(
+                    clap::Arg::new("paths")
+                        .help("Scripts or directories of .lox scripts")
+                        .num_args(0..)
+                        .default_value("benches")
+                )
+                .arg(
+                    clap::Arg::new("iterations")
+                        .long("iterations")
+                        .short('n')
+                        .help("Number of runs per script")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("5")
+                )
+                .arg(
+                    clap::Arg::new("format")
+                        .long("format")
+                        .help("Output format: \"text\" or \"json\"")
+                        .value_parser(["text", "json"])
+                        .default_value("text")
+                )
+        )
+        .subcommand(
+            clap::Command::new("fmt")
+                .about("Format scripts in place, or standard input to standard output when no paths are given")
+                .arg(
+                    clap::Arg::new("paths")
+                        .help("Scripts or directories of .lox scripts")
+                        .num_args(0..)
+                )
+                .arg(
+                    clap::Arg::new("check")
+                        .long("check")
+                        .help("Only list the scripts that are not formatted, and fail if there are any")
+                        .action(clap::ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            clap::Command::new("lsp")
+                .about("Run a language server over standard input and output")
+        )
         .get_matches()
 }
 
 fn main() {
     let args = args();
+    let mut limits = Limits::new();
+    limits.set_max_steps(args.get_one::<u64>("max-steps").copied());
+    limits.set_timeout(args.get_one::<u64>("timeout").map(|millis| Duration::from_millis(*millis)));
+    limits.set_max_memory(args.get_one::<usize>("max-memory").copied());
+
+    let capabilities = if args.get_flag("allow-all") {
+        Capabilities::all()
+    } else {
+        args.get_many::<Capability>("allow").into_iter().flatten().copied().collect()
+    };
+
+    let options = Options {
+        dialect: *args.get_one::<Dialect>("dialect").unwrap(),
+        backend: *args.get_one::<Backend>("backend").unwrap(),
+        optimize: args.get_flag("optimize"),
+        max_depth: args.get_one::<u64>("max-depth").map_or(DEFAULT_MAX_DEPTH, |&depth| depth as usize),
+        limits,
+        capabilities,
+        seed: args.get_one::<u64>("seed").copied()
+    };
+
+    let stack_size = options.max_depth.saturating_mul(STACK_PER_LEVEL).clamp(MIN_STACK, MAX_STACK);
+    let worker = thread::Builder::new()
+        .name("main".into())
+        .stack_size(stack_size)
+        .spawn(move || run(args, options));
+
+    let worker = match worker {
+        Ok(worker) => worker,
+        Err(err) => {
+            eprintln!("Error: Cannot start the interpreter with a {stack_size} byte stack: {err}");
+            process::exit(1);
+        }
+    };
+
+    if let Err(panic) = worker.join() {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+fn run(args: clap::ArgMatches, options: Options) {
+    match args.subcommand() {
+        Some(("bench", bench_args)) => {
+            run_bench(bench_args, options).unwrap();
+            return;
+        },
+        Some(("fmt", fmt_args)) => {
+            if !run_fmt(fmt_args, options).unwrap() {
+                process::exit(1);
+            }
+            return;
+        },
+        Some(("lsp", _)) => {
+            match run_lsp(options) {
+                Ok(true) => {},
+                Ok(false) => process::exit(1),
+                Err(err) => {
+                    eprintln!("{err}");
+                    process::exit(1);
+                }
+            }
+            return;
+        },
+        _ => {}
+    }
 
     if let Some(path) = args.get_one::<String>("file") {
-        run_file(path).unwrap();
+        if args.get_flag("print-ast") {
+            print_ast(path, options).unwrap();
+        } else if args.get_flag("disassemble") {
+            disassemble_file(path, options).unwrap();
+        } else {
+            let mut executor = options.executor();
+            run_file(path, executor.as_mut()).unwrap();
+
+            if args.get_flag("gc-stats") {
+                eprintln!("gc: {}", executor.gc_stats());
+            }
+        }
         return;
     }
-    
-    run_prompt().unwrap();
+
+    run_prompt(options.executor().as_mut()).unwrap();
 }
 
-fn run_file(path: &str) -> Result<(), Error> {
+fn run_file(path: &str, executor: &mut dyn Executor) -> Result<(), Error> {
     let code = fs::read_to_string(path)?;
-    Interpreter::new().run(&code)?;
+
+    if let Err(err) = executor.run(&code) {
+        match err.exit_code() {
+            Some(code) => process::exit(code),
+            None => return Err(err.into())
+        }
+    }
 
     Ok(())
 }
 
-fn run_prompt() -> Result<(), Error> {
+fn print_ast(path: &str, options: Options) -> Result<(), Error> {
+    let code = fs::read_to_string(path)?;
+    let scanner = Scanner::new(&code, options.dialect);
+    let mut tokens = scanner.tokens();
+    let mut parser = Parser::with_dialect(&code, &mut tokens, options.dialect);
+    parser.set_max_depth(options.max_depth);
+    let mut statements = parser.parse()?;
+
+    if options.optimize {
+        statements = Optimizer::new(options.dialect).optimize(&statements);
+    }
+
+    let mut printer = Printer::new();
+
+    for statement in statements.iter() {
+        println!("{}", printer.print_statement(statement));
+    }
+
+    Ok(())
+}
+
+fn disassemble_file(path: &str, options: Options) -> Result<(), Error> {
+    let code = fs::read_to_string(path)?;
+    let mut vm = Vm::with_dialect(options.dialect);
+    vm.set_optimize(options.optimize);
+    vm.set_max_depth(options.max_depth);
+    let chunk = vm.compile(&code)?;
+    print!("{}", disassembler::disassemble(&chunk, path));
+
+    Ok(())
+}
+
+fn run_prompt(executor: &mut dyn Executor) -> Result<(), Error> {
     let mut stdin = io::stdin().lock();
     let mut stdout = io::stdout();
     let mut buffer = String::new();
-    let mut interpreter = Interpreter::new();
+
+    // Ctrl-C cancels the line being evaluated, keeping everything defined so
+    // far. At the prompt the terminal already discards the typed input, so
+    // only a fresh prompt is needed.
+    let evaluating = Arc::new(AtomicBool::new(false));
+    let cancel = executor.cancel_handle();
+
+    ctrlc::set_handler({
+        let evaluating = evaluating.clone();
+
+        move || {
+            if evaluating.load(Ordering::SeqCst) {
+                cancel.cancel();
+            } else {
+                let mut stdout = io::stdout().lock();
+                let _ = write!(stdout, "\n> ");
+                let _ = stdout.flush();
+            }
+        }
+    })?;
 
     loop {
         write!(stdout.lock(), "> ")?;
         stdout.flush()?;
-        stdin.read_line(&mut buffer)?;
-        if let Err(err) = interpreter.run(&buffer) {
-            eprintln!("{err}");
+
+        if stdin.read_line(&mut buffer)? == 0 {
+            writeln!(stdout.lock())?;
+            return Ok(());
         }
+
+        // Drop a cancellation that arrived just as the previous line ended.
+        executor.cancel_handle().reset();
+        evaluating.store(true, Ordering::SeqCst);
+        let result = executor.run(&buffer);
+        evaluating.store(false, Ordering::SeqCst);
+
+        match result {
+            Err(err) if err.limit() == Some(Limit::Cancelled) => eprintln!("Error: Interrupted"),
+            Err(err) => match err.exit_code() {
+                Some(code) => process::exit(code),
+                None => eprintln!("{err}")
+            },
+            Ok(()) => {}
+        }
+
         buffer.clear();
     }
 }
+
+fn run_bench(args: &clap::ArgMatches, options: Options) -> Result<(), Error> {
+    let iterations = *args.get_one::<usize>("iterations").unwrap();
+    let mut scripts = Vec::new();
+
+    for path in args.get_many::<String>("paths").unwrap() {
+        collect_scripts(Path::new(path), &mut scripts)?;
+    }
+
+    let mut results = Vec::new();
+
+    // Every run gets a fresh executor, with what the script prints thrown
+    // away, so only scanning, parsing and running the script is measured.
+    for script in scripts {
+        let code = fs::read_to_string(&script)?;
+
+        let summary = bench::measure(iterations, || -> Result<(), Error> {
+            let mut executor = options.executor();
+            executor.set_output(Box::new(io::sink()));
+
+            executor.run(&code)
+                .map_err(|err| format!("benchmark {} failed: {err}", script.display()).into())
+        })?;
+
+        let name = script.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        results.push((name, summary));
+    }
+
+    match args.get_one::<String>("format").unwrap().as_str() {
+        "json" => print_bench_json(&results, options.backend),
+        _ => print_bench_text(&results)
+    }
+
+    Ok(())
+}
+
+/// Whether everything was formatted, or with `--check`, already was.
+fn run_fmt(args: &clap::ArgMatches, options: Options) -> Result<bool, Error> {
+    let mut formatter = Formatter::new(options.dialect);
+    formatter.set_max_depth(options.max_depth);
+    let check = args.get_flag("check");
+
+    let Some(paths) = args.get_many::<String>("paths") else {
+        let mut code = String::new();
+        io::stdin().read_to_string(&mut code)?;
+
+        return match formatter.format(&code) {
+            Ok(formatted) if check => Ok(formatted == code),
+            Ok(formatted) => {
+                print!("{formatted}");
+                Ok(true)
+            },
+            Err(err) => {
+                eprintln!("{err}");
+                Ok(false)
+            }
+        };
+    };
+
+    let mut scripts = Vec::new();
+
+    for path in paths {
+        collect_scripts(Path::new(path), &mut scripts)?;
+    }
+
+    let mut success = true;
+
+    for script in scripts {
+        let code = fs::read_to_string(&script)?;
+
+        match formatter.format(&code) {
+            Ok(formatted) if formatted == code => {},
+            Ok(_) if check => {
+                println!("{}", script.display());
+                success = false;
+            },
+            Ok(formatted) => fs::write(&script, formatted)?,
+            Err(err) => {
+                eprintln!("{}: {err}", script.display());
+                success = false;
+            }
+        }
+    }
+
+    Ok(success)
+}
+
+/// Whether the client shut the server down before it exited.
+fn run_lsp(options: Options) -> Result<bool, Error> {
+    let mut server = lsp::Server::new(options.dialect);
+    server.set_max_depth(options.max_depth);
+    server.run(io::stdin().lock(), io::stdout().lock())?;
+
+    Ok(server.is_shut_down())
+}
+
+fn collect_scripts(path: &Path, scripts: &mut Vec<PathBuf>) -> Result<(), Error> {
+    if !path.is_dir() {
+        scripts.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+
+    scripts.extend(entries.into_iter().filter(|path| path.extension().is_some_and(|ext| ext == "lox")));
+
+    Ok(())
+}
+
+fn print_bench_text(results: &[(String, Summary)]) {
+    let width = results.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("benchmark".len());
+
+    println!("{:width$}  {:>10}  {:>10}  {:>10}", "benchmark", "min", "median", "max");
+
+    for (name, summary) in results {
+        println!(
+            "{:width$}  {:>8.2}ms  {:>8.2}ms  {:>8.2}ms",
+            name,
+            millis(summary.min()),
+            millis(summary.median()),
+            millis(summary.max())
+        );
+    }
+}
+
+fn print_bench_json(results: &[(String, Summary)], backend: Backend) {
+    let entries: Vec<serde_json::Value> = results.iter()
+        .map(|(name, summary)| serde_json::json!({
+            "name": name,
+            "backend": backend.to_string(),
+            "runs": summary.runs(),
+            "min_ms": millis(summary.min()),
+            "median_ms": millis(summary.median()),
+            "max_ms": millis(summary.max())
+        }))
+        .collect();
+
+    println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,230 @@
+use super::{
+    value::{self, Value},
+    heap::Heap,
+    error::{Error, ErrorKind}
+};
+
+pub fn call_method(heap: &mut Heap, receiver: &Value, name: &str, arguments: &[Value]) -> Result<Value, Error> {
+    match receiver {
+        Value::String(string) => call_string_method(heap, string, name, arguments),
+        Value::List(list) => call_list_method(&list.items(), name, arguments),
+        _ => Err(runtime_error(format!("Value \"{receiver}\" has no method \"{name}\"")))
+    }
+}
+
+fn call_string_method(heap: &mut Heap, string: &str, name: &str, arguments: &[Value]) -> Result<Value, Error> {
+    let arguments = Arguments::new(name, arguments);
+
+    let value = match name {
+        "length" => {
+            arguments.expect(0, 0)?;
+            Value::Number(string.chars().count() as f64)
+        },
+        "substring" => {
+            arguments.expect(1, 2)?;
+            let length = string.chars().count();
+            let start = arguments.index(0)?.min(length);
+            let end = arguments.optional_index(1)?.unwrap_or(length).min(length);
+
+            Value::from(string.chars().skip(start).take(end.saturating_sub(start)).collect::<String>())
+        },
+        "slice" => {
+            arguments.expect(1, 2)?;
+            let length = string.chars().count() as f64;
+            let start = relative_index(arguments.integer(0)?, length);
+            let end = match arguments.get(1) {
+                Some(_) => relative_index(arguments.integer(1)?, length),
+                None => length as usize
+            };
+
+            Value::from(string.chars().skip(start).take(end.saturating_sub(start)).collect::<String>())
+        },
+        "split" => {
+            arguments.expect(1, 1)?;
+            let separator = arguments.string(0)?;
+
+            let parts: Vec<Value> = if separator.is_empty() {
+                string.chars().map(|c| Value::from(c.to_string())).collect()
+            } else {
+                string.split(separator).map(Value::from).collect()
+            };
+
+            heap.alloc_list(parts)
+        },
+        "join" => {
+            arguments.expect(1, 1)?;
+            let items = match arguments.get(0) {
+                Some(Value::List(list)) => list.items().clone(),
+                _ => return Err(arguments.type_error(0, "a list"))
+            };
+
+            let parts: Vec<String> = items.iter().map(Value::to_string).collect();
+            let separators = string.len().checked_mul(parts.len().saturating_sub(1));
+            let length = parts.iter().fold(separators, |length, part| length?.checked_add(part.len()));
+            value::check_string_length(length)?;
+            heap.reserve(length.unwrap_or_default())?;
+
+            Value::from(parts.join(string))
+        },
+        "upper" => {
+            arguments.expect(0, 0)?;
+            Value::from(string.to_uppercase())
+        },
+        "lower" => {
+            arguments.expect(0, 0)?;
+            Value::from(string.to_lowercase())
+        },
+        "trim" => {
+            arguments.expect(0, 0)?;
+            Value::from(string.trim())
+        },
+        "startsWith" => {
+            arguments.expect(1, 1)?;
+            Value::from(string.starts_with(arguments.string(0)?))
+        },
+        "endsWith" => {
+            arguments.expect(1, 1)?;
+            Value::from(string.ends_with(arguments.string(0)?))
+        },
+        "find" => {
+            arguments.expect(1, 1)?;
+            let needle = arguments.string(0)?;
+
+            match string.find(needle) {
+                Some(byte_index) => Value::Number(string[..byte_index].chars().count() as f64),
+                None => Value::Number(-1.0)
+            }
+        },
+        "replace" => {
+            arguments.expect(2, 2)?;
+            let from = arguments.string(0)?;
+            let to = arguments.string(1)?;
+
+            if from.is_empty() {
+                return Err(runtime_error("Argument 1 of method \"replace\" must not be empty".into()));
+            }
+
+            let matches = string.matches(from).count();
+            let length = matches.checked_mul(to.len()).and_then(|added| string.len().checked_add(added));
+            value::check_string_length(length)?;
+            heap.reserve(length.unwrap_or_default())?;
+
+            Value::from(string.replace(from, to))
+        },
+        "repeat" => {
+            arguments.expect(1, 1)?;
+            let count = arguments.index(0)?;
+            let length = string.len().checked_mul(count);
+            value::check_string_length(length)?;
+            heap.reserve(length.unwrap_or_default())?;
+
+            Value::from(string.repeat(count))
+        },
+        _ => return Err(runtime_error(format!("Undefined method \"{name}\" for string")))
+    };
+
+    Ok(value)
+}
+
+fn call_list_method(items: &[Value], name: &str, arguments: &[Value]) -> Result<Value, Error> {
+    let arguments = Arguments::new(name, arguments);
+
+    let value = match name {
+        "length" => {
+            arguments.expect(0, 0)?;
+            Value::Number(items.len() as f64)
+        },
+        "get" => {
+            arguments.expect(1, 1)?;
+            let index = arguments.index(0)?;
+
+            match items.get(index) {
+                Some(value) => value.clone(),
+                None => return Err(runtime_error(format!("Index {index} is out of bounds for list of length {}", items.len())))
+            }
+        },
+        _ => return Err(runtime_error(format!("Undefined method \"{name}\" for list")))
+    };
+
+    Ok(value)
+}
+
+struct Arguments<'a> {
+    method: &'a str,
+    values: &'a [Value]
+}
+
+impl<'a> Arguments<'a> {
+    fn new(method: &'a str, values: &'a [Value]) -> Self {
+        Self {
+            method,
+            values
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<&'a Value> {
+        self.values.get(index)
+    }
+
+    fn expect(&self, min: usize, max: usize) -> Result<(), Error> {
+        let count = self.values.len();
+
+        if count >= min && count <= max {
+            return Ok(());
+        }
+
+        let expected = if min == max {
+            format!("{min}")
+        } else {
+            format!("{min} to {max}")
+        };
+
+        Err(runtime_error(format!("Method \"{}\" expects {expected} arguments but got {count}", self.method)))
+    }
+
+    fn string(&self, index: usize) -> Result<&'a str, Error> {
+        match self.values.get(index) {
+            Some(Value::String(value)) => Ok(value),
+            _ => Err(self.type_error(index, "a string"))
+        }
+    }
+
+    fn integer(&self, index: usize) -> Result<f64, Error> {
+        match self.values.get(index) {
+            Some(Value::Number(value)) if value.fract() == 0.0 => Ok(*value),
+            _ => Err(self.type_error(index, "an integer"))
+        }
+    }
+
+    fn index(&self, index: usize) -> Result<usize, Error> {
+        match self.integer(index)? {
+            value if value >= 0.0 => Ok(value as usize),
+            _ => Err(self.type_error(index, "a non-negative integer"))
+        }
+    }
+
+    fn optional_index(&self, index: usize) -> Result<Option<usize>, Error> {
+        match self.values.get(index) {
+            Some(_) => Ok(Some(self.index(index)?)),
+            None => Ok(None)
+        }
+    }
+
+    fn type_error(&self, index: usize, expected: &str) -> Error {
+        runtime_error(format!("Argument {} of method \"{}\" must be {expected}", index + 1, self.method))
+    }
+}
+
+fn relative_index(index: f64, length: f64) -> usize {
+    if index < 0.0 {
+        (length + index).max(0.0) as usize
+    } else {
+        index.min(length) as usize
+    }
+}
+
+fn runtime_error(message: String) -> Error {
+    Error::new(
+        ErrorKind::RuntimeError { message }
+    )
+}
@@ -0,0 +1,116 @@
+use std::{env, fs, io};
+
+use super::{
+    value::{self, Value},
+    error::{Error, ErrorKind},
+    capabilities::{Capabilities, Capability},
+    host::Host
+};
+
+/// Calls the native function `name`. Natives that reach outside the
+/// interpreter fail unless their capability was granted.
+pub fn call_native(capabilities: Capabilities, host: &mut Host, name: &str, arguments: &[Value]) -> Result<Value, Error> {
+    let arguments = Arguments::new(name, arguments);
+
+    let value = match name {
+        "clock" => {
+            arguments.expect(0)?;
+            capabilities.check(Capability::Clock, name)?;
+
+            Value::Number(host.now().as_secs_f64())
+        },
+        "random" => {
+            arguments.expect(0)?;
+
+            Value::Number(host.random())
+        },
+        "env" => {
+            arguments.expect(1)?;
+            let variable = arguments.string(0)?;
+            capabilities.check(Capability::Env, name)?;
+
+            env::var(variable).map_or(Value::Null, Value::from)
+        },
+        "readFile" => {
+            arguments.expect(1)?;
+            let path = arguments.string(0)?;
+            capabilities.check(Capability::Files, name)?;
+
+            let length = fs::metadata(path).map(|metadata| usize::try_from(metadata.len()).ok());
+            value::check_string_length(length.unwrap_or(Some(0)))?;
+
+            match fs::read_to_string(path) {
+                Ok(contents) => Value::from(contents),
+                Err(err) => return Err(runtime_error(format!("Cannot read \"{path}\": {err}")))
+            }
+        },
+        "readLine" => {
+            arguments.expect(0)?;
+            capabilities.check(Capability::Stdin, name)?;
+
+            let mut line = String::new();
+
+            match io::stdin().read_line(&mut line) {
+                Ok(0) => Value::Null,
+                Ok(_) => Value::from(line.trim_end_matches(['\n', '\r'])),
+                Err(err) => return Err(runtime_error(format!("Cannot read standard input: {err}")))
+            }
+        },
+        "exit" => {
+            arguments.expect(1)?;
+            let code = arguments.status(0)?;
+            capabilities.check(Capability::Exit, name)?;
+
+            return Err(Error::new(ErrorKind::Exit { code }));
+        },
+        _ => return Err(runtime_error(format!("Undefined function \"{name}\"")))
+    };
+
+    Ok(value)
+}
+
+struct Arguments<'a> {
+    function: &'a str,
+    values: &'a [Value]
+}
+
+impl<'a> Arguments<'a> {
+    fn new(function: &'a str, values: &'a [Value]) -> Self {
+        Self {
+            function,
+            values
+        }
+    }
+
+    fn expect(&self, count: usize) -> Result<(), Error> {
+        if self.values.len() == count {
+            return Ok(());
+        }
+
+        Err(runtime_error(format!("Function \"{}\" expects {count} arguments but got {}", self.function, self.values.len())))
+    }
+
+    fn string(&self, index: usize) -> Result<&'a str, Error> {
+        match self.values.get(index) {
+            Some(Value::String(value)) => Ok(value),
+            _ => Err(self.type_error(index, "a string"))
+        }
+    }
+
+    fn status(&self, index: usize) -> Result<i32, Error> {
+        match self.values.get(index) {
+            Some(Value::Number(value)) if value.fract() == 0.0 && (0.0..=255.0).contains(value) => Ok(*value as i32),
+            _ => Err(self.type_error(index, "an integer from 0 to 255"))
+        }
+    }
+
+    fn type_error(&self, index: usize, expected: &str) -> Error {
+        runtime_error(format!("Argument {} of function \"{}\" must be {expected}", index + 1, self.function))
+    }
+}
+
+fn runtime_error(message: String) -> Error {
+    Error::new(
+        ErrorKind::RuntimeError { message }
+    )
+}
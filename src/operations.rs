@@ -0,0 +1,115 @@
+use super::{
+    dialect::Dialect,
+    value::{self, Value},
+    token::TokenType,
+    error::{Error, ErrorKind},
+    methods::call_method,
+    heap::Heap
+};
+
+pub fn binary(dialect: Dialect, operator: TokenType, left: Value, right: Value) -> Result<Value, Error> {
+    use TokenType::*;
+
+    if !dialect.implicit_conversions() {
+        return strict_binary(operator, left, right);
+    }
+
+    let value = match operator {
+        Minus => left.subtract(&right)?,
+        Slash => left.division(&right)?,
+        Star => left.mutiply(&right)?,
+        Plus => left.add(&right)?,
+        Greater => left.greater(&right)?,
+        GreaterEqual => left.greater_equal(&right)?,
+        Less => left.less(&right)?,
+        LessEqual => left.less_equal(&right)?,
+        EqualEqual => left.equal(&right)?,
+        BangEqual => left.not_equal(&right)?,
+        Comma => right,
+        _ => unreachable!()
+    };
+
+    Ok(value)
+}
+
+fn strict_binary(operator: TokenType, left: Value, right: Value) -> Result<Value, Error> {
+    use TokenType::*;
+
+    let numbers = matches!((&left, &right), (Value::Number(_), Value::Number(_)));
+    let strings = matches!((&left, &right), (Value::String(_), Value::String(_)));
+
+    let value = match operator {
+        EqualEqual => Value::from(left.identical(&right)),
+        BangEqual => Value::from(!left.identical(&right)),
+        Comma => right,
+        Plus if numbers || strings => left.add(&right)?,
+        Plus => return Err(runtime_error("Operands must be two numbers or two strings")),
+        _ if !numbers => return Err(runtime_error("Operands must be numbers")),
+        Minus => left.subtract(&right)?,
+        Slash => left.division(&right)?,
+        Star => left.mutiply(&right)?,
+        Greater => left.greater(&right)?,
+        GreaterEqual => left.greater_equal(&right)?,
+        Less => left.less(&right)?,
+        LessEqual => left.less_equal(&right)?,
+        _ => unreachable!()
+    };
+
+    Ok(value)
+}
+
+pub fn negate(dialect: Dialect, value: Value) -> Result<Value, Error> {
+    if !dialect.implicit_conversions() && !matches!(value, Value::Number(_)) {
+        return Err(runtime_error("Operand must be a number"));
+    }
+
+    match value.as_number()? {
+        Value::Number(number) => Ok(Value::Number(-number)),
+        _ => unreachable!()
+    }
+}
+
+pub fn is_truthy(dialect: Dialect, value: &Value) -> bool {
+    if dialect.falsy_zero_and_empty() {
+        value.as_boolean().is_true()
+    } else {
+        !matches!(value, Value::False | Value::Null)
+    }
+}
+
+pub fn stringify(dialect: Dialect, value: &Value) -> String {
+    match value {
+        Value::Null => dialect.null_keyword().into(),
+        value => value.to_string()
+    }
+}
+
+pub fn interpolate(parts: &[Value]) -> Result<Value, Error> {
+    let mut text = String::new();
+
+    for part in parts {
+        match part.as_string() {
+            Value::String(part) => {
+                value::check_string_length(text.len().checked_add(part.len()))?;
+                text.push_str(&part);
+            },
+            _ => unreachable!()
+        }
+    }
+
+    Ok(Value::from(text))
+}
+
+pub fn invoke(dialect: Dialect, heap: &mut Heap, object: &Value, name: &str, arguments: &[Value]) -> Result<Value, Error> {
+    if !dialect.builtin_methods() {
+        return Err(runtime_error("Only instances have properties"));
+    }
+
+    call_method(heap, object, name, arguments)
+}
+
+fn runtime_error(message: &str) -> Error {
+    Error::new(
+        ErrorKind::RuntimeError { message: message.into() }
+    )
+}
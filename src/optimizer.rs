@@ -0,0 +1,240 @@
+use super::{
+    dialect::Dialect,
+    value::Value,
+    token::TokenType,
+    operations,
+    ast::{
+        ExpressionVisitor,
+        StatementVisitor,
+        Expression,
+        Statement,
+        Binary,
+        Grouping,
+        Literal,
+        Unary,
+        Ternary,
+        Variable,
+        Assign,
+        Logical,
+        Interpolation,
+        MethodCall,
+        Call,
+        ExpressionStatement,
+        Print,
+        Var,
+        Block,
+        If,
+        While
+    }
+};
+
+/// Folds constant expressions and prunes branches whose condition is known
+/// at compile time. Folding that would fail (e.g. `1 - "x"`) is left in
+/// place so the error is still reported when the program runs.
+#[derive(Debug, Default)]
+pub struct Optimizer {
+    dialect: Dialect
+}
+
+impl Optimizer {
+    pub fn new(dialect: Dialect) -> Self {
+        Self { dialect }
+    }
+
+    pub fn optimize(&mut self, statements: &[Statement]) -> Vec<Statement> {
+        statements.iter().filter_map(|statement| statement.accept(self)).collect()
+    }
+
+    fn optimize_expression(&mut self, expression: &Expression) -> Expression {
+        expression.accept(self)
+    }
+
+    fn optimize_branch(&mut self, statement: &Statement) -> Statement {
+        statement.accept(self).unwrap_or_else(|| Statement::Block(Block::new(Vec::new())))
+    }
+
+    fn truthiness(&self, expression: &Expression) -> Option<bool> {
+        constant(expression).map(|value| operations::is_truthy(self.dialect, value))
+    }
+}
+
+fn constant(expression: &Expression) -> Option<&Value> {
+    match expression {
+        Expression::Literal(literal) => Some(literal.value()),
+        _ => None
+    }
+}
+
+fn literal(value: Value, line: usize) -> Expression {
+    Expression::Literal(Literal::new(value, line))
+}
+
+impl ExpressionVisitor<Expression> for Optimizer {
+    fn visit_binary(&mut self, expression: &Binary) -> Expression {
+        let left = self.optimize_expression(expression.left());
+        let right = self.optimize_expression(expression.right());
+        let operator = expression.operator();
+
+        if operator.token_type() == TokenType::Comma && constant(&left).is_some() {
+            return right;
+        }
+
+        if let (Some(lhs), Some(rhs)) = (constant(&left), constant(&right)) {
+            if let Ok(value) = operations::binary(self.dialect, operator.token_type(), lhs.clone(), rhs.clone()) {
+                return literal(value, operator.line());
+            }
+        }
+
+        Expression::Binary(Binary::new(left, *operator, right))
+    }
+
+    fn visit_grouping(&mut self, expression: &Grouping) -> Expression {
+        let inner = self.optimize_expression(expression.expression());
+
+        match inner {
+            Expression::Literal(_) => inner,
+            _ => Expression::Grouping(Grouping::new(inner))
+        }
+    }
+
+    fn visit_literal(&mut self, expression: &Literal) -> Expression {
+        Expression::Literal(expression.clone())
+    }
+
+    fn visit_unary(&mut self, expression: &Unary) -> Expression {
+        let right = self.optimize_expression(expression.right());
+
+        if let Some(value) = constant(&right) {
+            let folded = match expression.operator().token_type() {
+                TokenType::Minus => operations::negate(self.dialect, value.clone()).ok(),
+                TokenType::Bang => Some(Value::from(!operations::is_truthy(self.dialect, value))),
+                _ => None
+            };
+
+            if let Some(value) = folded {
+                return literal(value, expression.operator().line());
+            }
+        }
+
+        Expression::Unary(Unary::new(*expression.operator(), right))
+    }
+
+    fn visit_ternary(&mut self, expression: &Ternary) -> Expression {
+        let first = self.optimize_expression(expression.first());
+
+        match self.truthiness(&first) {
+            Some(true) => self.optimize_expression(expression.second()),
+            Some(false) => self.optimize_expression(expression.third()),
+            None => Expression::Ternary(
+                Ternary::new(
+                    *expression.operator(),
+                    first,
+                    self.optimize_expression(expression.second()),
+                    self.optimize_expression(expression.third())
+                )
+            )
+        }
+    }
+
+    fn visit_variable(&mut self, expression: &Variable) -> Expression {
+        Expression::Variable(expression.clone())
+    }
+
+    fn visit_assign(&mut self, expression: &Assign) -> Expression {
+        Expression::Assign(Assign::new(expression.name().clone(), self.optimize_expression(expression.value())))
+    }
+
+    fn visit_logical(&mut self, expression: &Logical) -> Expression {
+        let left = self.optimize_expression(expression.left());
+        let right = self.optimize_expression(expression.right());
+
+        let short_circuits = match expression.operator().token_type() {
+            TokenType::Or => self.truthiness(&left),
+            TokenType::And => self.truthiness(&left).map(|truthy| !truthy),
+            _ => unreachable!()
+        };
+
+        match short_circuits {
+            Some(true) => left,
+            Some(false) => right,
+            None => Expression::Logical(Logical::new(left, *expression.operator(), right))
+        }
+    }
+
+    fn visit_interpolation(&mut self, expression: &Interpolation) -> Expression {
+        let parts: Vec<Expression> = expression.parts().iter().map(|part| self.optimize_expression(part)).collect();
+        let values: Option<Vec<Value>> = parts.iter().map(|part| constant(part).cloned()).collect();
+
+        match values.map(|values| operations::interpolate(&values)) {
+            Some(Ok(value)) => literal(value, expression.locations().first().map_or(0, |(_, line)| *line)),
+            _ => Expression::Interpolation(expression.with_parts(parts))
+        }
+    }
+
+    fn visit_method_call(&mut self, expression: &MethodCall) -> Expression {
+        let object = self.optimize_expression(expression.object());
+        let arguments = expression.arguments().iter().map(|argument| self.optimize_expression(argument)).collect();
+
+        Expression::MethodCall(MethodCall::new(object, expression.name().clone(), *expression.token(), arguments))
+    }
+
+    fn visit_call(&mut self, expression: &Call) -> Expression {
+        let arguments = expression.arguments().iter().map(|argument| self.optimize_expression(argument)).collect();
+
+        Expression::Call(Call::new(expression.name().clone(), *expression.token(), arguments))
+    }
+}
+
+impl StatementVisitor<Option<Statement>> for Optimizer {
+    fn visit_expression_statement(&mut self, statement: &ExpressionStatement) -> Option<Statement> {
+        let expression = self.optimize_expression(statement.expression());
+
+        match expression {
+            Expression::Literal(_) => None,
+            expression => Some(Statement::ExpressionStatement(ExpressionStatement::new(expression, statement.line())))
+        }
+    }
+
+    fn visit_print(&mut self, statement: &Print) -> Option<Statement> {
+        Some(Statement::Print(Print::new(self.optimize_expression(statement.expression()), statement.line())))
+    }
+
+    fn visit_var(&mut self, statement: &Var) -> Option<Statement> {
+        let right = self.optimize_expression(statement.right());
+
+        Some(Statement::Var(Var::new(statement.name().clone(), right, statement.line())))
+    }
+
+    fn visit_block(&mut self, statement: &Block) -> Option<Statement> {
+        Some(Statement::Block(Block::new(self.optimize(statement.statements()))))
+    }
+
+    fn visit_if(&mut self, statement: &If) -> Option<Statement> {
+        let condition = self.optimize_expression(statement.condition());
+
+        match self.truthiness(&condition) {
+            Some(true) => statement.then_branch().accept(self),
+            Some(false) => statement.else_branch().and_then(|branch| branch.accept(self)),
+            None => Some(
+                Statement::If(
+                    If::new(
+                        condition,
+                        self.optimize_branch(statement.then_branch()),
+                        statement.else_branch().map(|branch| self.optimize_branch(branch)),
+                        statement.line()
+                    )
+                )
+            )
+        }
+    }
+
+    fn visit_while(&mut self, statement: &While) -> Option<Statement> {
+        let condition = self.optimize_expression(statement.condition());
+
+        if self.truthiness(&condition) == Some(false) {
+            return None;
+        }
+
+        Some(Statement::While(While::new(condition, self.optimize_branch(statement.body()), statement.line())))
+    }
+}
@@ -0,0 +1,41 @@
+use std::{fmt, io};
+
+use super::error::{Error, ErrorKind};
+
+/// Where `print` writes to: standard output unless the embedder redirects
+/// it, for example to capture what a script prints.
+pub struct Output {
+    writer: Box<dyn io::Write>
+}
+
+impl Output {
+    pub fn stdout() -> Self {
+        Self::new(Box::new(io::stdout()))
+    }
+
+    pub fn new(writer: Box<dyn io::Write>) -> Self {
+        Self {
+            writer
+        }
+    }
+
+    pub(super) fn print(&mut self, text: &str) -> Result<(), Error> {
+        writeln!(self.writer, "{text}").map_err(|err| {
+            Error::new(
+                ErrorKind::RuntimeError { message: format!("Cannot write output: {err}") }
+            )
+        })
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self::stdout()
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Output").finish_non_exhaustive()
+    }
+}
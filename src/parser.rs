@@ -8,9 +8,12 @@ term           → factor ( ( "-" | "+" ) factor )* ;
 factor         → unary ( ( "/" | "*" ) unary )* ;
 ternary        → unary ( ( "?" expression ":" expression ) )? ;
 unary          → ( "!" | "-" ) unary
-               | primary ;
+               | call ;
+call           → primary ( "." IDENTIFIER "(" arguments? ")" )* ;
+arguments      → assignment ( "," assignment )* ;
 primary        → NUMBER | STRING | "true" | "false" | "nil"
-               | "(" expression ")" ;
+               | "(" expression ")" | interpolation ;
+interpolation  → ( INTERPOLATION expression "}" )+ STRING ;
 */
 
 use std::iter;
@@ -18,7 +21,10 @@ use std::iter;
 use crate::token::TokenOption;
 
 use super::{
-    token::{Token, TokenType},
+    dialect::Dialect,
+    interner::Interner,
+    token::{Token, TokenType, Span},
+    cst::{Node, NodeKind, Builder, Checkpoint},
     ast::{
         Expression,
         Literal,
@@ -35,31 +41,76 @@ use super::{
         Block,
         If,
         Logical,
-        While
+        While,
+        Interpolation as InterpolationExpression,
+        MethodCall,
+        Call
     },
     error::{Error, ErrorKind},
     value::Value,
-    utils::parse_number
+    utils::parse_number_literal
 };
 
 use TokenType::*;
 
 type TokenResult = Result<Token, Error>;
 
+/// Default limit on how deeply statements and expressions may nest. Low
+/// enough that a program at the limit runs on a standard 2 MiB thread
+/// stack, even in a debug build.
+pub const MAX_DEPTH: usize = 32;
+
+/// How many operators a chain such as `1 + 2 + 3` may have for each level
+/// of `max_depth`. Chains are parsed in a loop but still make the tree one
+/// level deeper per operator, so they get their own, larger limit.
+pub const LINKS_PER_LEVEL: usize = 16;
+
+/// Parses the tokens scanned from `source`; the source is needed to read
+/// identifier names and literals back from the token spans.
 pub struct Parser<'a> {
-    tokens: Tokens<'a>
+    tokens: Tokens<'a>,
+    source: &'a str,
+    dialect: Dialect,
+    interner: Interner,
+    depth: usize,
+    links: usize,
+    max_depth: usize
 }
 
 impl<'a> Parser<'a> {
-    pub fn new<T: iter::Iterator<Item = TokenResult>>(tokens: &'a mut T) -> Self {
-        let tokens = Tokens::new(tokens);
+    pub fn new<T: iter::Iterator<Item = TokenResult>>(source: &'a str, tokens: &'a mut T) -> Self {
+        Self::with_dialect(source, tokens, Dialect::default())
+    }
+
+    pub fn with_dialect<T: iter::Iterator<Item = TokenResult>>(source: &'a str, tokens: &'a mut T, dialect: Dialect) -> Self {
+        let tokens = Tokens::new(source, tokens);
 
         Self {
-            tokens
+            tokens,
+            source,
+            dialect,
+            interner: Interner::new(),
+            depth: 0,
+            links: 0,
+            max_depth: MAX_DEPTH
         }
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Box<dyn Statement>>, Error> {
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Parses into a concrete syntax tree instead of an AST. The tree covers
+    /// every byte of the source when the tokens come from
+    /// `Scanner::tokens_with_trivia`.
+    pub fn parse_tree(&mut self) -> Result<Node, Error> {
+        self.tokens.builder = Some(Builder::new());
+        self.parse()?;
+
+        Ok(self.tokens.builder.take().unwrap().build())
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Statement>, Error> {
         self.tokens.next()?;
         let mut result = Vec::new();
 
@@ -76,76 +127,83 @@ impl<'a> Parser<'a> {
         Ok(result)
     }
 
-    fn declaration(&mut self) -> Result<Box<dyn Statement>, Error> {
+    fn declaration(&mut self) -> Result<Statement, Error> {
         if self.tokens.token_match(&[Var]) {
-            self.tokens.next()?;
             self.var_declaration()
         } else {
             self.statement()
         }
     }
 
-    fn var_declaration(&mut self) -> Result<Box<dyn Statement>, Error> {
+    fn var_declaration(&mut self) -> Result<Statement, Error> {
+        self.node(NodeKind::Var, Self::var_declaration_body)
+    }
+
+    fn var_declaration_body(&mut self) -> Result<Statement, Error> {
+        let line = self.line();
+        self.tokens.next()?;
         let token = self.tokens.consume(&[Identifier], "Expect variable name")?;
 
-        let name = token.lexeme().unwrap().into();
+        let name = self.interner.intern(token.lexeme(self.source));
         let initializer = if let Some(Equal) = self.tokens.current().token_type() {
             self.tokens.next()?;
             self.expression()?
         } else {
-            Box::new(Literal::new(Value::Null))
+            Expression::Literal(Literal::new(Value::Null, line))
         };
 
         self.tokens.consume(&[Semicolon], "Expect \";\" after expression")?;
 
 
-        Ok(Box::new(Var::new(name, initializer)))
+        Ok(Statement::Var(Var::new(name, initializer, line)))
     }
 
-    fn statement(&mut self) -> Result<Box<dyn Statement>, Error> {
+    fn statement(&mut self) -> Result<Statement, Error> {
+        self.nested(Self::statement_body)
+    }
+
+    fn statement_body(&mut self) -> Result<Statement, Error> {
         if self.tokens.token_match(&[For]) {
-            self.tokens.next()?;
-            return self.for_statement();
+            return self.node(NodeKind::For, Self::for_statement);
         }
 
         if self.tokens.token_match(&[If]) {
-            self.tokens.next()?;
-            return self.if_statement();
+            return self.node(NodeKind::If, Self::if_statement);
         }
 
         if self.tokens.token_match(&[Print]) {
-            self.tokens.next()?;
-            return self.print_statement();
+            return self.node(NodeKind::Print, Self::print_statement);
         }
 
         if self.tokens.token_match(&[While]) {
-            self.tokens.next()?;
-            return self.while_statement();
+            return self.node(NodeKind::While, Self::while_statement);
         }
 
         if self.tokens.token_match(&[LeftBrace]) {
-            self.tokens.next()?;
-            return self.block();
+            return self.node(NodeKind::Block, Self::block);
         }
         
         self.expression_statement()
     }
 
-    fn for_statement(&mut self) -> Result<Box<dyn Statement>, Error> {
+    // The statements below start at their keyword or brace.
+
+    fn for_statement(&mut self) -> Result<Statement, Error> {
+        let line = self.line();
+        self.tokens.next()?;
         self.tokens.consume(&[LeftParen], "Expect \"(\' after \"for\"")?;
 
         let initializer = if self.tokens.token_match(&[Semicolon]) {
             self.tokens.next()?;
             None
         } else if self.tokens.token_match(&[Var]) {
-            self.tokens.next()?;
             Some(self.var_declaration()?)
         } else {
             Some(self.expression_statement()?)
         };
 
         let condition = if self.tokens.token_match(&[Semicolon]) {
-            Box::new(Literal::new(Value::True))
+            Expression::Literal(Literal::new(Value::True, self.line()))
         } else {
             self.expression()?
         };
@@ -155,25 +213,25 @@ impl<'a> Parser<'a> {
         let increment = if self.tokens.token_match(&[RightParen]) {
             None
         } else {
-            Some(self.expression()?)
+            Some((self.line(), self.expression()?))
         };
 
         self.tokens.consume(&[RightParen], "expect \")\" after clauses")?;
 
         let mut body = self.statement()?;
 
-        if let Some(increment) = increment {
-            body = Box::new(
-                Block::new(vec![body, Box::new(ExpressionStatement::new(increment))])
+        if let Some((increment_line, increment)) = increment {
+            body = Statement::Block(
+                Block::new(vec![body, Statement::ExpressionStatement(ExpressionStatement::new(increment, increment_line))])
             );
         }
 
-        body = Box::new(
-            While::new(condition, body)
+        body = Statement::While(
+            While::new(condition, body, line)
         );
 
         if let Some(initializer) = initializer {
-            body = Box::new(
+            body = Statement::Block(
                 Block::new(vec![initializer, body])
             );
         }
@@ -181,17 +239,21 @@ impl<'a> Parser<'a> {
         Ok(body)
     }
 
-    fn while_statement(&mut self) -> Result<Box<dyn Statement>, Error> {
+    fn while_statement(&mut self) -> Result<Statement, Error> {
+        let line = self.line();
+        self.tokens.next()?;
         self.tokens.consume(&[LeftParen], "Expect \"(\' after \"while\"")?;
         let condition = self.expression()?;
         self.tokens.consume(&[RightParen], "Expect \")\" after while condition")?;
 
         let body = self.statement()?;
 
-        Ok(Box::new(While::new(condition, body)))
+        Ok(Statement::While(While::new(condition, body, line)))
     }
 
-    fn if_statement(&mut self) -> Result<Box<dyn Statement>, Error> {
+    fn if_statement(&mut self) -> Result<Statement, Error> {
+        let line = self.line();
+        self.tokens.next()?;
         self.tokens.consume(&[LeftParen], "Expect \"(\' after \"if\"")?;
         let condition = self.expression()?;
         self.tokens.consume(&[RightParen], "Expect \")\" after if condition")?;
@@ -204,10 +266,11 @@ impl<'a> Parser<'a> {
             None
         };
 
-        Ok(Box::new(If::new(condition, then_branch, else_branch)))
+        Ok(Statement::If(If::new(condition, then_branch, else_branch, line)))
     }
 
-    fn block(&mut self) -> Result<Box<dyn Statement>, Error> {
+    fn block(&mut self) -> Result<Statement, Error> {
+        self.tokens.next()?;
         let mut statements = Vec::new();
 
         while !self.tokens.token_match(&[RightBrace]) {
@@ -216,31 +279,42 @@ impl<'a> Parser<'a> {
 
         self.tokens.consume(&[RightBrace], "Expect \"}\" after block")?;
 
-        Ok(Box::new(Block::new(statements)))
+        Ok(Statement::Block(Block::new(statements)))
     }
 
-    fn print_statement(&mut self) -> Result<Box<dyn Statement>, Error> {
+    fn print_statement(&mut self) -> Result<Statement, Error> {
+        let line = self.line();
+        self.tokens.next()?;
         let expression = self.expression()?;
         self.tokens.consume(&[Semicolon], "Expect \";\" after value")?;
 
-        Ok(Box::new(Print::new(expression)))
+        Ok(Statement::Print(Print::new(expression, line)))
     }
 
-    fn expression_statement(&mut self) -> Result<Box<dyn Statement>, Error> {
-        let expression = self.expression()?;
-        self.tokens.consume(&[Semicolon], "Expect \";\" after expression")?;
+    fn expression_statement(&mut self) -> Result<Statement, Error> {
+        self.node(NodeKind::ExpressionStatement, |parser| {
+            let line = parser.line();
+            let expression = parser.expression()?;
+            parser.tokens.consume(&[Semicolon], "Expect \";\" after expression")?;
 
-        Ok(Box::new(ExpressionStatement::new(expression)))
+            Ok(Statement::ExpressionStatement(ExpressionStatement::new(expression, line)))
+        })
     }
 
-    fn expression(&mut self) -> Result<Box<dyn Expression>, Error> {
+    fn expression(&mut self) -> Result<Expression, Error> {
+        let checkpoint = self.checkpoint();
         let mut expression = self.assignment()?;
 
-        while self.tokens.token_match(&[Comma]) {
+        let links = self.links;
+
+        while self.dialect.comma_operator() && self.tokens.token_match(&[Comma]) {
+            self.link()?;
+            self.start_at(checkpoint, NodeKind::Binary);
             let operator = self.tokens.next()?.unwrap();
             let right = self.assignment()?;
         
-            expression = Box::new(
+            self.finish();
+            expression = Expression::Binary(
                 Binary::new(
                     expression,
                     operator,
@@ -249,31 +323,33 @@ impl<'a> Parser<'a> {
             );
         }
 
+        self.links = links;
+
         Ok(expression)
     }
 
-    fn assignment(&mut self) -> Result<Box<dyn Expression>, Error> {
+    fn assignment(&mut self) -> Result<Expression, Error> {
+        self.nested(Self::assignment_body)
+    }
+
+    fn assignment_body(&mut self) -> Result<Expression, Error> {
+        let checkpoint = self.checkpoint();
         let expression = self.or()?;
 
         if self.tokens.token_match(&[Equal]) {
+            self.start_at(checkpoint, NodeKind::Assign);
             let token = self.tokens.next()?;
 
-            match expression.as_variable() {
-                Some(variable) => {
+            match expression {
+                Expression::Variable(variable) => {
                     let name = variable.name().clone();
                     let value = self.assignment()?;
+                    self.finish();
 
-                    return Ok(Box::new(Assign::new(name, value)))
+                    return Ok(Expression::Assign(Assign::new(name, value)))
                 },
-                None => {
-                    return Err(
-                        Error::new(
-                            ErrorKind::ParserError {
-                                token,
-                                message: "Invalid assignment target".into()
-                            }
-                        )
-                    )
+                _ => {
+                    return Err(self.tokens.error(token, "Invalid assignment target".into()))
                 }
             }
         }
@@ -281,38 +357,60 @@ impl<'a> Parser<'a> {
         Ok(expression)
     }
 
-    fn or(&mut self) -> Result<Box<dyn Expression>, Error> {
+    fn or(&mut self) -> Result<Expression, Error> {
+        let checkpoint = self.checkpoint();
         let mut expression = self.and()?;
 
+        let links = self.links;
+
         while self.tokens.token_match(&[Or]) {
+            self.link()?;
+            self.start_at(checkpoint, NodeKind::Logical);
             let operator = self.tokens.next()?.unwrap();
             let right = self.and()?;
-            expression = Box::new(Logical::new(expression, operator, right))
+            self.finish();
+            expression = Expression::Logical(Logical::new(expression, operator, right))
         }
 
+        self.links = links;
+
         Ok(expression)
     }
 
-    fn and(&mut self) -> Result<Box<dyn Expression>, Error> {
+    fn and(&mut self) -> Result<Expression, Error> {
+        let checkpoint = self.checkpoint();
         let mut expression = self.equaity()?;
 
+        let links = self.links;
+
         while self.tokens.token_match(&[And]) {
+            self.link()?;
+            self.start_at(checkpoint, NodeKind::Logical);
             let operator = self.tokens.next()?.unwrap();
             let right = self.equaity()?;
-            expression = Box::new(Logical::new(expression, operator, right))
+            self.finish();
+            expression = Expression::Logical(Logical::new(expression, operator, right))
         }
 
+        self.links = links;
+
         Ok(expression)
     }
 
-    fn equaity(&mut self) -> Result<Box<dyn Expression>, Error> {
+    fn equaity(&mut self) -> Result<Expression, Error> {
+        let checkpoint = self.checkpoint();
         let mut expression = self.comparison()?;
 
+        let links = self.links;
+
         while self.tokens.token_match(&[BangEqual, EqualEqual]) {
+            self.link()?;
+            self.start_at(checkpoint, NodeKind::Binary);
             let operator = self.tokens.next()?.unwrap();
             let right = self.comparison()?;
 
-            expression = Box::new(
+            self.finish();
+            expression = Expression::Binary(
                 Binary::new(
                     expression,
                     operator,
@@ -321,17 +419,25 @@ impl<'a> Parser<'a> {
             );
         }
 
+        self.links = links;
+
         Ok(expression)
     }
 
-    fn comparison(&mut self) -> Result<Box<dyn Expression>, Error> {
+    fn comparison(&mut self) -> Result<Expression, Error> {
+        let checkpoint = self.checkpoint();
         let mut expression = self.term()?;
 
+        let links = self.links;
+
         while self.tokens.token_match(&[Greater, GreaterEqual, Less, LessEqual]) {
+            self.link()?;
+            self.start_at(checkpoint, NodeKind::Binary);
             let operator = self.tokens.next()?.unwrap();
             let right = self.term()?;
 
-            expression = Box::new(
+            self.finish();
+            expression = Expression::Binary(
                 Binary::new(
                     expression,
                     operator,
@@ -340,17 +446,25 @@ impl<'a> Parser<'a> {
             );
         }
 
+        self.links = links;
+
         Ok(expression)
     }
 
-    fn term(&mut self) -> Result<Box<dyn Expression>, Error> {
+    fn term(&mut self) -> Result<Expression, Error> {
+        let checkpoint = self.checkpoint();
         let mut expression = self.factor()?;
 
+        let links = self.links;
+
         while self.tokens.token_match(&[Minus, Plus]) {
+            self.link()?;
+            self.start_at(checkpoint, NodeKind::Binary);
             let operator = self.tokens.next()?.unwrap();
             let right = self.factor()?;
 
-            expression = Box::new(
+            self.finish();
+            expression = Expression::Binary(
                 Binary::new(
                     expression,
                     operator,
@@ -359,17 +473,25 @@ impl<'a> Parser<'a> {
             );
         }
 
+        self.links = links;
+
         Ok(expression)
     }
 
-    fn factor(&mut self) -> Result<Box<dyn Expression>, Error> {
+    fn factor(&mut self) -> Result<Expression, Error> {
+        let checkpoint = self.checkpoint();
         let mut expression = self.ternary()?;
 
+        let links = self.links;
+
         while self.tokens.token_match(&[Slash, Star]) {
+            self.link()?;
+            self.start_at(checkpoint, NodeKind::Binary);
             let operator = self.tokens.next()?.unwrap();
             let right = self.ternary()?;
 
-            expression = Box::new(
+            self.finish();
+            expression = Expression::Binary(
                 Binary::new(
                     expression,
                     operator,
@@ -378,19 +500,24 @@ impl<'a> Parser<'a> {
             );
         }
 
+        self.links = links;
+
         Ok(expression)
     }
 
-    fn ternary(&mut self) -> Result<Box<dyn Expression>, Error> {
+    fn ternary(&mut self) -> Result<Expression, Error> {
+        let checkpoint = self.checkpoint();
         let mut expression = self.unary()?;
 
-        if self.tokens.token_match(&[Query]) {
+        if self.dialect.ternary_operator() && self.tokens.token_match(&[Query]) {
+            self.start_at(checkpoint, NodeKind::Ternary);
             let operator = self.tokens.next()?.unwrap();
             let second = self.expression()?;
             self.tokens.consume(&[Colon], "Expected \":\" after first expression")?;
             let third = self.expression()?;
+            self.finish();
 
-            expression = Box::new(
+            expression = Expression::Ternary(
                 Ternary::new(
                     operator,
                     expression,
@@ -403,73 +530,314 @@ impl<'a> Parser<'a> {
         Ok(expression)
     }
 
-    fn unary(&mut self) -> Result<Box<dyn Expression>, Error> {
+    fn unary(&mut self) -> Result<Expression, Error> {
         if self.tokens.token_match(&[Bang, Minus]) {
-            let operator = self.tokens.next()?.unwrap();
-            let right = self.unary()?;
+            self.node(NodeKind::Unary, |parser| {
+                let operator = parser.tokens.next()?.unwrap();
+                let right = parser.nested(Self::unary)?;
 
-            Ok(Box::new(Unary::new(operator, right)))
+                Ok(Expression::Unary(Unary::new(operator, right)))
+            })
         } else {
-            self.primary()
+            self.call()
+        }
+    }
+
+    fn call(&mut self) -> Result<Expression, Error> {
+        let checkpoint = self.checkpoint();
+        let expression = self.primary()?;
+        let mut expression = self.native_call(checkpoint, expression)?;
+
+        let links = self.links;
+
+        while self.tokens.token_match(&[Dot]) {
+            self.link()?;
+            self.start_at(checkpoint, NodeKind::MethodCall);
+            self.tokens.next()?;
+            let token = self.tokens.consume(&[Identifier], "Expect method name after \".\"")?;
+            let name = self.interner.intern(token.lexeme(self.source));
+            self.tokens.consume(&[LeftParen], "Expect \"(\" after method name")?;
+            let arguments = self.arguments()?;
+
+            self.finish();
+            expression = Expression::MethodCall(MethodCall::new(expression, name, token, arguments));
         }
+
+        self.links = links;
+
+        Ok(expression)
+    }
+
+    /// A call of a native function by name. There are no function values,
+    /// so only a name can be called.
+    fn native_call(&mut self, checkpoint: Option<Checkpoint>, expression: Expression) -> Result<Expression, Error> {
+        let (Expression::Variable(variable), Some(LeftParen)) = (&expression, self.tokens.current().token_type()) else {
+            return Ok(expression);
+        };
+
+        let token = self.tokens.previous.unwrap();
+        let name = variable.name().clone();
+
+        self.start_at(checkpoint, NodeKind::Call);
+        self.tokens.next()?;
+        let arguments = self.arguments()?;
+        self.finish();
+
+        Ok(Expression::Call(Call::new(name, token, arguments)))
     }
 
-    fn primary(&mut self) -> Result<Box<dyn Expression>, Error> {
+    /// Parses call arguments after the opening parenthesis.
+    fn arguments(&mut self) -> Result<Vec<Expression>, Error> {
+        let mut arguments = Vec::new();
+
+        if !self.tokens.token_match(&[RightParen]) {
+            loop {
+                arguments.push(self.assignment()?);
+
+                if !self.tokens.token_match(&[Comma]) {
+                    break;
+                }
+
+                self.tokens.next()?;
+            }
+        }
+
+        self.tokens.consume(&[RightParen], "Expect \")\" after arguments")?;
+
+        Ok(arguments)
+    }
+
+    fn primary(&mut self) -> Result<Expression, Error> {
+        let kind = match self.tokens.current().token_type() {
+            Some(False | True | Null | Number | String) => NodeKind::Literal,
+            Some(Interpolation) => NodeKind::Interpolation,
+            Some(Identifier) => NodeKind::Variable,
+            Some(LeftParen) => NodeKind::Grouping,
+            _ => return self.primary_body()
+        };
+
+        self.node(kind, Self::primary_body)
+    }
+
+    fn primary_body(&mut self) -> Result<Expression, Error> {
         let token = self.tokens.next()?;
+        let line = token.map_or(0, |token| token.line());
 
         match token.token_type() {
-            Some(False) => Ok(Box::new(Literal::new(Value::False))),
-            Some(True) => Ok(Box::new(Literal::new(Value::True))),
-            Some(Null) => Ok(Box::new(Literal::new(Value::Null))),
-            Some(Number | String) => Ok(Box::new(Literal::new(parse_value(token.unwrap())?))),
-            Some(Identifier) => Ok(Box::new(Variable::new(token.lexeme().unwrap().into()))),
+            Some(False) => Ok(Expression::Literal(Literal::new(Value::False, line))),
+            Some(True) => Ok(Expression::Literal(Literal::new(Value::True, line))),
+            Some(Null) => Ok(Expression::Literal(Literal::new(Value::Null, line))),
+            Some(Number | String) => Ok(Expression::Literal(Literal::new(self.parse_value(token.unwrap())?, line))),
+            Some(Interpolation) => self.interpolation(token.unwrap()),
+            Some(Identifier) => {
+                let name = self.interner.intern(token.unwrap().lexeme(self.source));
+
+                Ok(Expression::Variable(Variable::new(name)))
+            },
             Some(LeftParen) => {
                 let expression = self.expression()?;
                 self.tokens.consume(&[RightParen], "Expect \")\" after expression")?;
 
-                Ok(Box::new(Grouping::new(expression)))
+                Ok(Expression::Grouping(Grouping::new(expression)))
             },
             Some(_) => {
-                let token = token.unwrap();
-
-                Err(
-                    Error::new(
-                        ErrorKind::ParserError {
-                            message: format!("Unexpeceted token \"{token}\""),
-                            token: Some(token)
-                        }
-                    )
-                )
+                let message = format!("Unexpeceted token \"{}\"", token.unwrap().lexeme(self.source));
+
+                Err(self.tokens.error(token, message))
             },
-            None => Err(
-                Error::new(
-                    ErrorKind::ParserError {
-                        token: None,
-                        message: format!("Expect token")
-                    }
-                )
-            )
+            None => Err(self.tokens.error(None, "Expect token".into()))
+        }
+    }
+
+    fn interpolation(&mut self, token: Token) -> Result<Expression, Error> {
+        let mut parts: Vec<Expression> = Vec::new();
+        let mut locations = Vec::new();
+        let mut token = token;
+
+        loop {
+            let token_type = token.token_type();
+
+            if !string_content(token.lexeme(self.source)).is_empty() {
+                parts.push(Expression::Literal(Literal::new(self.parse_value(token)?, token.line())));
+                locations.push((token.span(), token.line()));
+            }
+
+            if token_type == String {
+                break;
+            }
+
+            let start = self.tokens.current().map_or(token.span().end(), |first| first.span().start());
+            let line = self.tokens.current().map_or(token.line(), Token::line);
+
+            parts.push(self.expression()?);
+            let end = self.tokens.previous.map_or(start, |last| last.span().end());
+            locations.push((Span::new(start, end), line));
+
+            self.tokens.consume(&[InterpolationEnd], "Expect \"}\" after interpolated expression")?;
+            token = self.tokens.consume(&[Interpolation, String], "Expect string after interpolation")?;
+        }
+
+        Ok(Expression::Interpolation(InterpolationExpression::new(parts, locations)))
+    }
+
+    /// Parses something that becomes a node of `kind` in the syntax tree.
+    fn node<T>(&mut self, kind: NodeKind, parse: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        if let Some(builder) = &mut self.tokens.builder {
+            builder.start(kind);
+        }
+
+        let result = parse(self)?;
+        self.finish();
+
+        Ok(result)
+    }
+
+    /// Line of the next token, or of the last one at the end of the input.
+    fn line(&mut self) -> usize {
+        let previous = self.tokens.previous.map_or(1, |token| token.line());
+
+        self.tokens.current().map_or(previous, Token::line)
+    }
+
+    fn checkpoint(&mut self) -> Option<Checkpoint> {
+        self.tokens.builder.as_mut().map(Builder::checkpoint)
+    }
+
+    /// Starts a node around everything parsed since `checkpoint`; used where
+    /// the first child is parsed before it is known what the node is.
+    fn start_at(&mut self, checkpoint: Option<Checkpoint>, kind: NodeKind) {
+        if let (Some(builder), Some(checkpoint)) = (&mut self.tokens.builder, checkpoint) {
+            builder.start_at(checkpoint, kind);
         }
     }
+
+    fn finish(&mut self) {
+        if let Some(builder) = &mut self.tokens.builder {
+            builder.finish();
+        }
+    }
+
+    /// Parses one nesting level deeper, failing once `max_depth` is reached
+    /// so that deeply nested input reports an error instead of overflowing
+    /// the stack here or in later passes over the tree.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        let depth = self.depth;
+        self.enter()?;
+        let result = parse(self);
+        self.depth = depth;
+
+        result
+    }
+
+    fn enter(&mut self) -> Result<(), Error> {
+        self.depth += 1;
+
+        if self.depth > self.max_depth {
+            let message = format!("Too deeply nested, the limit is {} levels", self.max_depth);
+            let token = self.tokens.current().copied();
+
+            return Err(self.tokens.error(token, message));
+        }
+
+        Ok(())
+    }
+
+    /// Counts one more operator in the chains being parsed. Nested chains
+    /// add up, since they all deepen the same tree.
+    fn link(&mut self) -> Result<(), Error> {
+        self.links += 1;
+
+        if self.links > self.max_depth.saturating_mul(LINKS_PER_LEVEL) {
+            let message = format!("Too many chained operators, the limit is {}", self.max_depth.saturating_mul(LINKS_PER_LEVEL));
+            let token = self.tokens.current().copied();
+
+            return Err(self.tokens.error(token, message));
+        }
+
+        Ok(())
+    }
+
+    fn parse_value(&mut self, token: Token) -> Result<Value, Error> {
+        let lexeme = token.lexeme(self.source);
+
+        let value = match token.token_type() {
+            Number => {
+                match parse_number_literal(lexeme) {
+                    Ok(value) => Value::Number(value),
+                    Err(err) => return Err(self.tokens.error(Some(token), err.to_string()))
+                }
+            },
+            String | Interpolation => {
+                Value::String(self.interner.intern(string_content(lexeme)))
+            },
+            _ => return Err(self.tokens.error(Some(token), format!("Token {} has no value", token.token_type())))
+        };
+
+        Ok(value)
+    }
+}
+
+/// Text of a string segment without its delimiters: a segment starts with
+/// `"` or follows an interpolation's `}`, and ends with `"` or `${`.
+fn string_content(lexeme: &str) -> &str {
+    let lexeme = lexeme.strip_suffix("${")
+        .or_else(|| lexeme.strip_suffix('"'))
+        .unwrap_or(lexeme);
+
+    lexeme.strip_prefix('"').unwrap_or(lexeme)
 }
 
 struct Tokens<'a> {
     inner: &'a mut dyn iter::Iterator<Item = TokenResult>,
-    current: Option<Token>
+    source: &'a str,
+    current: Option<Token>,
+    /// The last token consumed.
+    previous: Option<Token>,
+    builder: Option<Builder>
 }
 
 impl<'a> Tokens<'a> {
-    fn new<T: iter::Iterator<Item = TokenResult>>(inner: &'a mut T) -> Self {
+    fn new<T: iter::Iterator<Item = TokenResult>>(source: &'a str, inner: &'a mut T) -> Self {
         Self {
             inner,
-            current: None
+            source,
+            current: None,
+            previous: None,
+            builder: None
         }
     }
 
+    fn error(&self, token: Option<Token>, message: std::string::String) -> Error {
+        Error::new(
+            ErrorKind::ParserError {
+                token: token.map(|token| (token.lexeme(self.source).to_string(), token.line())),
+                span: token.map(|token| token.span()),
+                message
+            }
+        )
+    }
+
+    /// Consumes the current token. Trivia is skipped, and only goes into the
+    /// syntax tree if one is being built.
     fn next(&mut self) -> Result<Option<Token>, Error> {
         let current = self.current.take();
-        let token = self.inner.next().transpose()?;
-        self.current = token;
+        self.previous = current.or(self.previous);
+
+        if let (Some(builder), Some(token)) = (&mut self.builder, current) {
+            builder.token(token);
+        }
+
+        self.current = loop {
+            match self.inner.next().transpose()? {
+                Some(token) if token.token_type().is_trivia() => {
+                    if let Some(builder) = &mut self.builder {
+                        builder.trivia(token);
+                    }
+                },
+                token => break token
+            }
+        };
+
         Ok(current)
     }
 
@@ -481,14 +849,7 @@ impl<'a> Tokens<'a> {
         let token = self.next()?;
 
         let token = if let Some(token) = token { token } else {
-            return Err(
-                Error::new(
-                    ErrorKind::ParserError {
-                        token,
-                        message: err_message.into()
-                    }
-                )
-            );
+            return Err(self.error(None, err_message.into()));
         };
 
         let matched = variants.iter().any(|v| *v == token.token_type());
@@ -496,14 +857,7 @@ impl<'a> Tokens<'a> {
         if matched {
             Ok(token)
         } else {
-            Err(
-                Error::new(
-                    ErrorKind::ParserError {
-                        token: Some(token),
-                        message: err_message.into()
-                    }
-                )
-            )
+            Err(self.error(Some(token), err_message.into()))
         }
     }
 
@@ -519,52 +873,3 @@ impl<'a> Tokens<'a> {
         variants.iter().any(|v| *v == token.token_type())
     }
 }
-
-fn parse_value(token: Token) -> Result<Value, Error> {
-    match token.token_type() {
-        String | Number => {},
-        _ => return Err(
-            Error::new(
-                ErrorKind::ParserError {
-                    token: Some(token.clone()),
-                    message: format!("Token {} has no value", token.token_type())
-                }
-            )
-        )
-    }
-
-    let value = match token.lexeme() {
-        None => return Err(
-            Error::new(
-                ErrorKind::ParserError {
-                    token: Some(token.clone()),
-                    message: format!("Token {} without value", token)
-                }
-            )
-        ),
-        Some(value) => value
-    };
-
-    let value = match token.token_type() {
-        Number => {
-            match parse_number(value) {
-                Ok(value) => Value::Number(value),
-                Err(err) => return Err(
-                    Error::new(
-                        ErrorKind::ParserError {
-                            token: Some(token.clone()),
-                            message: err.to_string()
-                        }
-                    )
-                )
-            }
-        },
-        String => {
-            Value::String(value.into())
-        },
-        _ => panic!()
-    };
-
-    Ok(value)
-}
-
@@ -0,0 +1,202 @@
+use std::collections;
+
+use super::ast::{
+    ExpressionVisitor,
+    StatementVisitor,
+    Expression,
+    Statement,
+    Binary,
+    Grouping,
+    Literal,
+    Unary,
+    Ternary,
+    Slot,
+    Variable,
+    Assign,
+    Logical,
+    Interpolation,
+    MethodCall,
+    Call,
+    ExpressionStatement,
+    Print,
+    Var,
+    Block,
+    If,
+    While
+};
+
+/// Rewrites a parsed program so that every local variable access carries the
+/// `Slot` it lives in. Names that are not declared in an enclosing block stay
+/// unresolved and are looked up as globals at runtime.
+#[derive(Debug, Default)]
+pub struct Resolver {
+    scopes: Vec<collections::HashMap<String, usize>>
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn resolve(&mut self, statements: &[Statement]) -> Vec<Statement> {
+        statements.iter().map(|statement| statement.accept(self)).collect()
+    }
+
+    fn resolve_expression(&mut self, expression: &Expression) -> Expression {
+        expression.accept(self)
+    }
+
+    fn resolve_statement(&mut self, statement: &Statement) -> Statement {
+        statement.accept(self)
+    }
+
+    fn lookup(&self, name: &str) -> Option<Slot> {
+        self.scopes.iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| scope.get(name).map(|index| Slot::new(depth, *index)))
+    }
+
+    fn declare(&mut self, name: &str) -> Option<usize> {
+        let scope = self.scopes.last_mut()?;
+        let next = scope.len();
+
+        Some(*scope.entry(name.into()).or_insert(next))
+    }
+}
+
+impl ExpressionVisitor<Expression> for Resolver {
+    fn visit_binary(&mut self, expression: &Binary) -> Expression {
+        Expression::Binary(
+            Binary::new(
+                self.resolve_expression(expression.left()),
+                *expression.operator(),
+                self.resolve_expression(expression.right())
+            )
+        )
+    }
+
+    fn visit_grouping(&mut self, expression: &Grouping) -> Expression {
+        Expression::Grouping(Grouping::new(self.resolve_expression(expression.expression())))
+    }
+
+    fn visit_literal(&mut self, expression: &Literal) -> Expression {
+        Expression::Literal(expression.clone())
+    }
+
+    fn visit_unary(&mut self, expression: &Unary) -> Expression {
+        Expression::Unary(
+            Unary::new(*expression.operator(), self.resolve_expression(expression.right()))
+        )
+    }
+
+    fn visit_ternary(&mut self, expression: &Ternary) -> Expression {
+        Expression::Ternary(
+            Ternary::new(
+                *expression.operator(),
+                self.resolve_expression(expression.first()),
+                self.resolve_expression(expression.second()),
+                self.resolve_expression(expression.third())
+            )
+        )
+    }
+
+    fn visit_variable(&mut self, expression: &Variable) -> Expression {
+        let name = expression.name().clone();
+
+        match self.lookup(&name) {
+            Some(slot) => Expression::Variable(Variable::local(name, slot)),
+            None => Expression::Variable(Variable::new(name))
+        }
+    }
+
+    fn visit_assign(&mut self, expression: &Assign) -> Expression {
+        let name = expression.name().clone();
+        let value = self.resolve_expression(expression.value());
+
+        match self.lookup(&name) {
+            Some(slot) => Expression::Assign(Assign::local(name, slot, value)),
+            None => Expression::Assign(Assign::new(name, value))
+        }
+    }
+
+    fn visit_logical(&mut self, expression: &Logical) -> Expression {
+        Expression::Logical(
+            Logical::new(
+                self.resolve_expression(expression.left()),
+                *expression.operator(),
+                self.resolve_expression(expression.right())
+            )
+        )
+    }
+
+    fn visit_interpolation(&mut self, expression: &Interpolation) -> Expression {
+        let parts = expression.parts().iter().map(|part| self.resolve_expression(part)).collect();
+
+        Expression::Interpolation(expression.with_parts(parts))
+    }
+
+    fn visit_method_call(&mut self, expression: &MethodCall) -> Expression {
+        let object = self.resolve_expression(expression.object());
+        let arguments = expression.arguments().iter().map(|argument| self.resolve_expression(argument)).collect();
+
+        Expression::MethodCall(MethodCall::new(object, expression.name().clone(), *expression.token(), arguments))
+    }
+
+    fn visit_call(&mut self, expression: &Call) -> Expression {
+        let arguments = expression.arguments().iter().map(|argument| self.resolve_expression(argument)).collect();
+
+        Expression::Call(Call::new(expression.name().clone(), *expression.token(), arguments))
+    }
+}
+
+impl StatementVisitor<Statement> for Resolver {
+    fn visit_expression_statement(&mut self, statement: &ExpressionStatement) -> Statement {
+        Statement::ExpressionStatement(
+            ExpressionStatement::new(self.resolve_expression(statement.expression()), statement.line())
+        )
+    }
+
+    fn visit_print(&mut self, statement: &Print) -> Statement {
+        Statement::Print(Print::new(self.resolve_expression(statement.expression()), statement.line()))
+    }
+
+    fn visit_var(&mut self, statement: &Var) -> Statement {
+        let name = statement.name().clone();
+        let right = self.resolve_expression(statement.right());
+
+        match self.declare(&name) {
+            Some(index) => Statement::Var(Var::local(name, index, right, statement.line())),
+            None => Statement::Var(Var::new(name, right, statement.line()))
+        }
+    }
+
+    fn visit_block(&mut self, statement: &Block) -> Statement {
+        self.scopes.push(collections::HashMap::new());
+        let statements = self.resolve(statement.statements());
+        self.scopes.pop();
+
+        Statement::Block(Block::new(statements))
+    }
+
+    fn visit_if(&mut self, statement: &If) -> Statement {
+        Statement::If(
+            If::new(
+                self.resolve_expression(statement.condition()),
+                self.resolve_statement(statement.then_branch()),
+                statement.else_branch().map(|branch| self.resolve_statement(branch)),
+                statement.line()
+            )
+        )
+    }
+
+    fn visit_while(&mut self, statement: &While) -> Statement {
+        Statement::While(
+            While::new(
+                self.resolve_expression(statement.condition()),
+                self.resolve_statement(statement.body()),
+                statement.line()
+            )
+        )
+    }
+}
@@ -1,24 +1,71 @@
-use std::{iter, str};
+use std::{borrow::Cow, io, iter};
 
 use super::{
-    token::{TokenType, Token},
+    dialect::Dialect,
+    token::{TokenType, Token, Span},
     error::{Error, ErrorKind}
 };
 
+const KEYWORDS: [(&str, TokenType); 15] = [
+    ("and", TokenType::And),
+    ("class", TokenType::Class),
+    ("else", TokenType::Else),
+    ("false", TokenType::False),
+    ("for", TokenType::For),
+    ("fun", TokenType::Fun),
+    ("if", TokenType::If),
+    ("or", TokenType::Or),
+    ("print", TokenType::Print),
+    ("return", TokenType::Return),
+    ("super", TokenType::Super),
+    ("this", TokenType::This),
+    ("true", TokenType::True),
+    ("var", TokenType::Var),
+    ("while", TokenType::While)
+];
+
 #[derive(Debug)]
-pub struct Scanner {
-    source: String
+pub struct Scanner<'a> {
+    source: Cow<'a, str>,
+    dialect: Dialect
 }
 
-impl Scanner {
-    pub fn from_str(source: &str) -> Self {
+impl<'a> Scanner<'a> {
+    pub fn new(source: &'a str, dialect: Dialect) -> Self {
         Self {
-            source: source.into()
+            source: Cow::Borrowed(source),
+            dialect
         }
     }
 
-    pub fn tokens(&self) -> ScannerIter {
-        ScannerIter::new(self.source.chars())
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(source: &'a str) -> Self {
+        Self::new(source, Dialect::default())
+    }
+
+    /// Reads the whole of `reader` into a buffer owned by the scanner
+    /// before scanning starts. Tokens refer to the source by span, so the
+    /// scanner does not stream its input.
+    pub fn read_to_end<R: io::Read>(mut reader: R, dialect: Dialect) -> io::Result<Scanner<'static>> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+
+        Ok(Scanner {
+            source: Cow::Owned(source),
+            dialect
+        })
+    }
+
+    pub fn tokens(&self) -> ScannerIter<'_> {
+        ScannerIter::new(&self.source, self.dialect)
+    }
+
+    /// Like `tokens`, but also yields whitespace and comments, so that the
+    /// tokens cover every byte of the source.
+    pub fn tokens_with_trivia(&self) -> ScannerIter<'_> {
+        let mut tokens = ScannerIter::new(&self.source, self.dialect);
+        tokens.trivia = true;
+        tokens
     }
 
     pub fn source_ref(&self) -> &str {
@@ -27,55 +74,117 @@ impl Scanner {
 }
 
 pub struct ScannerIter<'a> {
-    source: str::Chars<'a>,
-    buffer: Vec<char>,
-    char_index: usize,
-    line: usize
+    source: &'a str,
+    dialect: Dialect,
+    start: usize,
+    current: usize,
+    line: usize,
+    interpolations: Vec<usize>,
+    resume_string: bool,
+    trivia: bool
 }
 
 impl<'a> ScannerIter<'a> {
-    fn new(source: str::Chars<'a>) -> Self {
+    fn new(source: &'a str, dialect: Dialect) -> Self {
         Self {
             source,
-            buffer: Vec::new(),
-            char_index: 0,
-            line: 1
+            dialect,
+            start: 0,
+            current: 0,
+            line: 1,
+            interpolations: Vec::new(),
+            resume_string: false,
+            trivia: false
+        }
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    fn peek(&self) -> Option<char> {
+        match self.source.as_bytes().get(self.current) {
+            Some(byte) if byte.is_ascii() => Some(*byte as char),
+            Some(_) => self.source[self.current..].chars().next(),
+            None => None
         }
     }
 
-    fn buffer_char(&mut self, c: char) {
-        self.buffer.push(c);
-        self.char_index -= 1;
+    fn peek_next(&self) -> Option<char> {
+        self.source[self.current..].chars().nth(1)
     }
 
     fn next_char(&mut self) -> Option<char> {
-        self.char_index += 1;
+        let c = self.peek()?;
+        self.current += c.len_utf8();
 
-        if let Some(c) = self.buffer.pop() {
-            return Some(c);
+        Some(c)
+    }
+
+    fn next_char_if(&mut self, expected: impl Fn(char) -> bool) -> Option<char> {
+        match self.peek() {
+            Some(c) if expected(c) => {
+                self.current += c.len_utf8();
+                Some(c)
+            },
+            _ => None
         }
+    }
 
-        self.source.next()
+    fn lexeme(&self) -> &'a str {
+        &self.source[self.start..self.current]
+    }
+
+    fn token(&self, token_type: TokenType) -> Token {
+        Token::new(token_type, Span::new(self.start, self.current), self.line)
     }
 
     fn next_token(&mut self) -> Result<Option<Token>, Error> {
+        if self.resume_string {
+            self.resume_string = false;
+            self.start = self.current;
+            return Ok(Some(self.scan_string()?));
+        }
+
         let token = loop {
+            self.start = self.current;
             let c = self.next_char();
 
             match c {
+                None if !self.interpolations.is_empty() => {
+                    return Err(self.error("Unterminated string interpolation".into()));
+                },
                 None => break None,
-                Some('(') => break Some(Token::new(TokenType::LeftParen, None, self.line)),
-                Some(')') => break Some(Token::new(TokenType::RightParen, None, self.line)),
-                Some('{') => break Some(Token::new(TokenType::LeftBrace, None, self.line)),
-                Some('}') => break Some(Token::new(TokenType::RightBrace, None, self.line)),
-                Some('.') => break Some(Token::new(TokenType::Dot, None, self.line)),
-                Some('-') => break Some(Token::new(TokenType::Minus, None, self.line)),
-                Some('+') => break Some(Token::new(TokenType::Plus, None, self.line)),
-                Some(';') => break Some(Token::new(TokenType::Semicolon, None, self.line)),
-                Some('*') => break Some(Token::new(TokenType::Star, None, self.line)),
-                Some(',') => break Some(Token::new(TokenType::Comma, None, self.line)),
-                Some('?') => break Some(Token::new(TokenType::Query, None, self.line)),
-                Some(':') => break Some(Token::new(TokenType::Colon, None, self.line)),
+                Some('(') => break Some(self.token(TokenType::LeftParen)),
+                Some(')') => break Some(self.token(TokenType::RightParen)),
+                Some('{') => {
+                    if let Some(depth) = self.interpolations.last_mut() {
+                        *depth += 1;
+                    }
+
+                    break Some(self.token(TokenType::LeftBrace))
+                },
+                Some('}') => {
+                    match self.interpolations.last_mut() {
+                        Some(0) => {
+                            self.interpolations.pop();
+                            self.resume_string = true;
+                            break Some(self.token(TokenType::InterpolationEnd))
+                        },
+                        Some(depth) => *depth -= 1,
+                        None => {}
+                    }
+
+                    break Some(self.token(TokenType::RightBrace))
+                },
+                Some('.') => break Some(self.token(TokenType::Dot)),
+                Some('-') => break Some(self.token(TokenType::Minus)),
+                Some('+') => break Some(self.token(TokenType::Plus)),
+                Some(';') => break Some(self.token(TokenType::Semicolon)),
+                Some('*') => break Some(self.token(TokenType::Star)),
+                Some(',') => break Some(self.token(TokenType::Comma)),
+                Some('?') => break Some(self.token(TokenType::Query)),
+                Some(':') => break Some(self.token(TokenType::Colon)),
                 Some('!') => break Some(self.scan_op_equal(TokenType::Bang, TokenType::BangEqual)),
                 Some('=') => break Some(self.scan_op_equal(TokenType::Equal, TokenType::EqualEqual)),
                 Some('>') => break Some(self.scan_op_equal(TokenType::Greater, TokenType::GreaterEqual)),
@@ -85,22 +194,19 @@ impl<'a> ScannerIter<'a> {
                     if token.is_some() { break token; }
                 },
                 Some('"') => break Some(self.scan_string()?),
-                Some(c) if c.is_digit(10) => break Some(self.scan_number(c)),
-                Some(c) if is_identifier_char(c)/* && !c.is_digit(10) */ => break Some(self.scan_identifier(c)),
+                Some(c) if c.is_ascii_digit() => break Some(self.scan_number(c)?),
+                Some(c) if is_identifier_char(c) => break Some(self.scan_identifier()),
                 Some(c) if c.is_whitespace() => {
                     if c == '\n' {
                         self.line += 1;
                     }
+
+                    if self.trivia {
+                        break Some(self.scan_whitespace());
+                    }
                 },
                 Some(c) => {
-                    return Err(
-                        Error::new(
-                            ErrorKind::ScannerError {
-                                line: self.line,
-                                message: format!("Unexpected character \"{c}\"")
-                            }
-                        )
-                    );
+                    return Err(self.error(format!("Unexpected character \"{c}\"")));
                 }
             };
         };
@@ -109,44 +215,42 @@ impl<'a> ScannerIter<'a> {
     }
 
     fn scan_op_equal(&mut self, op: TokenType, op_equal: TokenType) -> Token {
-        let c = self.next_char();
-
-        match c {
-            Some('=') => Token::new(op_equal, None, self.line),
-            _ => {
-                if let Some(c) = c { self.buffer_char(c); }
-                Token::new(op, None, self.line)
-            }
+        match self.next_char_if(|c| c == '=') {
+            Some(_) => self.token(op_equal),
+            None => self.token(op)
         }
     }
 
     fn scan_slash(&mut self) -> Option<Token> {
-        let c = self.next_char();
-
-        match c {
+        match self.next_char_if(|c| c == '/' || c == '*') {
             Some('/') => self.scan_single_line_comment(),
-            Some('*') => self.scan_multi_line_comment(),
-            _ => {
-                if let Some(c) = c { self.buffer_char(c); }
-                Some(Token::new(TokenType::Slash, None, self.line))
-            }
+            Some(_) => self.scan_multi_line_comment(),
+            None => Some(self.token(TokenType::Slash))
         }
     }
 
-    fn scan_single_line_comment(&mut self) -> Option<Token> {
-        loop {
-            match self.next_char() {
-                Some('\n') => {
-                    self.line += 1;
-                    return None
-                },
-                None => { return None; },
-                _ => {}
+    fn scan_whitespace(&mut self) -> Token {
+        while let Some(c) = self.next_char_if(char::is_whitespace) {
+            if c == '\n' {
+                self.line += 1;
             }
         }
+
+        self.token(TokenType::Whitespace)
     }
 
+    /// The line break after the comment is left to be scanned as whitespace.
+    fn scan_single_line_comment(&mut self) -> Option<Token> {
+        while self.next_char_if(|c| c != '\n').is_some() {}
+
+        self.comment()
+    }
+
+    /// Comments nest; the depth is counted rather than recursed on, so that
+    /// deeply nested openers cannot overflow the stack.
     fn scan_multi_line_comment(&mut self) -> Option<Token> {
+        let mut depth = 1usize;
+
         loop {
             match self.next_char() {
                 Some('\n') => { self.line += 1; },
@@ -155,7 +259,14 @@ impl<'a> ScannerIter<'a> {
 
                     match c {
                         Some('\n') => { self.line += 1; },
-                        Some('/') | None => return None,
+                        Some('/') => {
+                            depth -= 1;
+
+                            if depth == 0 {
+                                return self.comment();
+                            }
+                        },
+                        None => return self.comment(),
                         _ => {}
                     }
                 }
@@ -164,121 +275,144 @@ impl<'a> ScannerIter<'a> {
 
                     match c {
                         Some('\n') => { self.line += 1; }
-                        Some('*') => { self.scan_multi_line_comment(); },
-                        None => return None,
+                        Some('*') => { depth += 1; },
+                        None => return self.comment(),
                         _ => {}
                     }
                 }
-                None => { return None; },
+                None => { return self.comment(); },
                 _ => {}
             }
         }
     }
 
-    fn scan_string(&mut self) -> Result<Token, Error> {
-        let mut value = String::new();
+    fn comment(&self) -> Option<Token> {
+        self.trivia.then(|| self.token(TokenType::Comment))
+    }
 
+    fn scan_string(&mut self) -> Result<Token, Error> {
         loop {
-            let c = self.next_char();
-
-            match c {
-                Some('\"') => break,
-                Some(c) => {
-                    if c == '\n' {
-                        self.line += 1;
+            match self.next_char() {
+                Some('"') => break,
+                Some('$') if self.dialect.string_interpolation() => {
+                    if self.next_char_if(|c| c == '{').is_some() {
+                        self.interpolations.push(0);
+                        return Ok(self.token(TokenType::Interpolation));
                     }
-
-                    value.push(c);
                 },
-                None => {
-                    return Err(
-                        Error::new(
-                            ErrorKind::ScannerError {
-                                 line: self.line,
-                                 message: "Unterminated string".into()
-                            }
-                        )
-                    )
-                }
+                Some('\n') => self.line += 1,
+                Some(_) => {},
+                None => return Err(self.error("Unterminated string".into()))
             }
         }
 
-        Ok(Token::new(TokenType::String, Some(value), self.line))
+        Ok(self.token(TokenType::String))
     }
 
-    fn scan_number(&mut self, first: char) -> Token {
-        let mut value: String = first.into();
-        let mut have_dot = false;
+    /// Scans a number literal. Letters, digits and "_" right after it are
+    /// an error rather than the start of a name, and a "." only belongs to
+    /// the number when a digit follows, so "1.upper()" is a method call.
+    fn scan_number(&mut self, first: char) -> Result<Token, Error> {
+        let radix = match self.peek() {
+            Some('x' | 'X') => Some(16),
+            Some('o' | 'O') => Some(8),
+            Some('b' | 'B') => Some(2),
+            _ => None
+        };
 
-        loop {
-            let c = self.next_char();
+        match radix {
+            Some(radix) if first == '0' && self.dialect.extended_number_literals() => {
+                self.next_char();
 
-            match c {
-                Some('.') => {
-                    if have_dot {
-                        self.buffer_char('.');
-                        break;
-                    }
+                // A separator may follow the prefix, as in "0x_FF".
+                if self.scan_digits(radix, true)? == 0 {
+                    return Err(self.error(format!("Expect digits after \"{}\"", self.lexeme())));
+                }
+            },
+            _ => self.scan_decimal()?
+        }
 
-                    value.push('.');
-                    have_dot = true;
-                },
-                Some(c ) if c.is_digit(10) => {
-                    value.push(c);
-                },
-                c @ _ => {
-                    if let Some(c) = c {
-                        self.buffer_char(c);
-                    }
+        if let Some(c) = self.peek().filter(|c| is_identifier_char(*c)) {
+            return Err(self.error(format!("Invalid digit \"{c}\" in number \"{}\"", self.lexeme())));
+        }
 
-                    break;
-                }
+        Ok(self.token(TokenType::Number))
+    }
+
+    fn scan_decimal(&mut self) -> Result<(), Error> {
+        self.scan_digits(10, true)?;
+
+        if self.peek() == Some('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
+            self.next_char();
+            self.scan_digits(10, false)?;
+        }
+
+        if self.dialect.extended_number_literals() && self.next_char_if(|c| c == 'e' || c == 'E').is_some() {
+            self.next_char_if(|c| c == '+' || c == '-');
+
+            if self.scan_digits(10, false)? == 0 {
+                return Err(self.error(format!("Expect digits in exponent of \"{}\"", self.lexeme())));
             }
         }
 
-        Token::new(TokenType::Number, Some(value), self.line)
+        Ok(())
     }
 
-    fn scan_identifier(&mut self, c: char) -> Token {
-        let mut value: String = c.into();
+    fn scan_digits(&mut self, radix: u32, after_digit: bool) -> Result<usize, Error> {
+        let mut count = 0;
+        let mut after_digit = after_digit;
 
         loop {
-            let c = self.next_char();
-
-            match c {
-                Some(c) if is_identifier_char(c) => {
-                    value.push(c);
-                }
-                c @ _ => {
-                    if let Some(c) = c {
-                        self.buffer_char(c);
+            match self.peek() {
+                Some(c) if c.is_digit(radix) => {
+                    self.next_char();
+                    count += 1;
+                    after_digit = true;
+                },
+                Some('_') if self.dialect.extended_number_literals() => {
+                    self.next_char();
+
+                    match self.peek() {
+                        Some(c) if after_digit && c.is_digit(radix) => {},
+                        _ => {
+                            return Err(self.error(format!("Digit separator \"_\" must be between digits in \"{}\"", self.lexeme())));
+                        }
                     }
+                },
+                _ => break
+            }
+        }
 
-                    break;
-                }
+        Ok(count)
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::new(
+            ErrorKind::ScannerError {
+                line: self.line,
+                span: Span::new(self.start, self.current),
+                message
             }
+        )
+    }
+
+    fn scan_identifier(&mut self) -> Token {
+        while self.next_char_if(is_identifier_char).is_some() {}
+
+        let value = self.lexeme();
+        let is_keyword = |keyword: &str| if self.dialect.case_insensitive_keywords() {
+            value.eq_ignore_ascii_case(keyword)
+        } else {
+            value == keyword
+        };
+
+        if is_keyword(self.dialect.null_keyword()) {
+            return self.token(TokenType::Null);
         }
 
-        let key_word = value.to_lowercase();
-
-        match key_word.as_str() {
-            "and"       => Token::new(TokenType::And, None, self.line),
-            "class"     => Token::new(TokenType::Class, None, self.line),
-            "else"      => Token::new(TokenType::Else, None, self.line),
-            "false"     => Token::new(TokenType::False, None, self.line),
-            "for"       => Token::new(TokenType::For, None, self.line),
-            "fun"       => Token::new(TokenType::Fun, None, self.line),
-            "if"        => Token::new(TokenType::If, None, self.line),
-            "null"      => Token::new(TokenType::Null, None, self.line),
-            "or"        => Token::new(TokenType::Or, None, self.line),
-            "print"     => Token::new(TokenType::Print, None, self.line),
-            "return"    => Token::new(TokenType::Return, None, self.line),
-            "super"     => Token::new(TokenType::Super, None, self.line),
-            "this"      => Token::new(TokenType::This, None, self.line),
-            "true"      => Token::new(TokenType::True, None, self.line),
-            "var"       => Token::new(TokenType::Var, None, self.line),
-            "while"     => Token::new(TokenType::While, None, self.line),
-            _           => Token::new(TokenType::Identifier, Some(value), self.line)
+        match KEYWORDS.iter().find(|(keyword, _)| is_keyword(keyword)) {
+            Some((_, token_type)) => self.token(*token_type),
+            None => self.token(TokenType::Identifier)
         }
     }
 }
@@ -298,4 +432,3 @@ impl<'a> iter::Iterator for ScannerIter<'a> {
 fn is_identifier_char(c: char) -> bool {
     c.is_alphanumeric() || c == '_'
 }
-
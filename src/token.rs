@@ -41,7 +41,18 @@ pub enum TokenType {
     Number,
     Identifier,
     Query,
-    Colon
+    Colon,
+    Interpolation,
+    InterpolationEnd,
+    Whitespace,
+    Comment
+}
+
+impl TokenType {
+    /// Whitespace and comments, only scanned in lossless mode.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenType::Whitespace | TokenType::Comment)
+    }
 }
 
 impl fmt::Display for TokenType {
@@ -89,22 +100,60 @@ impl fmt::Display for TokenType {
             Identifier => write!(f, "Identifier"),
             Query => write!(f, "Query"),
             Colon => write!(f, "Colon"),
+            Interpolation => write!(f, "Interpolation"),
+            InterpolationEnd => write!(f, "InterpolationEnd"),
+            Whitespace => write!(f, "Whitespace"),
+            Comment => write!(f, "Comment"),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Byte range of a token in the source it was scanned from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Span {
+    start: usize,
+    end: usize
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self {
+            start,
+            end
+        }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// A token refers to its text by `Span` instead of owning it; use `lexeme`
+/// with the scanned source to get the text back.
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Token {
     token_type: TokenType,
-    lexeme: Option<String>,
+    span: Span,
     line: usize
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: Option<String>, line: usize) -> Self {
+    pub fn new(token_type: TokenType, span: Span, line: usize) -> Self {
         Self {
             token_type,
-            lexeme,
+            span,
             line
         }
     }
@@ -113,8 +162,13 @@ impl Token {
         self.token_type
     }
 
-    pub fn lexeme(&self) -> Option<&str> {
-        self.lexeme.as_ref().map(String::as_str)
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Source text of the token, including the quotes and `${` of strings.
+    pub fn lexeme<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.span.start..self.span.end]
     }
 
     pub fn line(&self) -> usize {
@@ -164,25 +218,14 @@ impl fmt::Display for Token {
             While => write!(f, "while"),
             Query => write!(f, "?"),
             Colon => write!(f, ":"),
-            String => {
-                let value = self.lexeme().unwrap();
-                write!(f, "\"{}\"", value)
-            },
-            Number => {
-                let number = self.lexeme().unwrap();
-                write!(f, "{}", number)
-            },
-            Identifier => {
-                let iden = self.lexeme().unwrap();
-                write!(f, "{}", iden)
-            }
+            InterpolationEnd => write!(f, "}}"),
+            String | Interpolation | Number | Identifier | Whitespace | Comment => write!(f, "{}", self.token_type)
         }
     }
 }
 
 pub trait TokenOption {
     fn token_type(&self) -> Option<TokenType>;
-    fn lexeme(&self) -> Option<&str>;
     fn line(&self) -> Option<usize>;
 }
 
@@ -191,10 +234,6 @@ impl TokenOption for Option<Token> {
         self.as_ref().map(Token::token_type)
     }
 
-    fn lexeme(&self) -> Option<&str> {
-        self.as_ref().map(Token::lexeme).flatten()
-    }
-
     fn line(&self) -> Option<usize> {
         self.as_ref().map(Token::line)
     }
@@ -205,10 +244,6 @@ impl TokenOption for Option<&Token> {
         self.map(Token::token_type)
     }
 
-    fn lexeme(&self) -> Option<&str> {
-        self.map(Token::lexeme).flatten()
-    }
-
     fn line(&self) -> Option<usize> {
         self.map(Token::line)
     }
@@ -0,0 +1,403 @@
+use super::{
+    dialect::Dialect,
+    error::{Error, ErrorKind},
+    value::Value,
+    token::TokenType,
+    ast::{
+        ExpressionVisitor,
+        StatementVisitor,
+        Expression,
+        Statement,
+        Binary,
+        Grouping,
+        Literal,
+        Unary,
+        Ternary,
+        Variable,
+        Assign,
+        Logical,
+        Interpolation,
+        MethodCall,
+        Call,
+        ExpressionStatement,
+        Print,
+        Var,
+        Block,
+        If,
+        While
+    }
+};
+
+const INDENT: &str = "    ";
+
+/// How tightly an expression binds, from loosest to tightest, following the
+/// grammar in `parser`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Comma,
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Ternary,
+    Unary,
+    Call,
+    Primary
+}
+
+/// Turns a syntax tree back into source code that parses to the same tree.
+/// `Grouping` nodes are printed as written; other parentheses are only
+/// added where precedence requires them, which can only happen for trees
+/// that were not produced by the parser.
+///
+/// Values the optimizer folds into literals print as code that evaluates
+/// to them, such as `(0 / 0)` for NaN, and may parse to a different tree.
+/// List values have no such code and are reported as errors.
+#[derive(Debug)]
+pub struct Unparser {
+    dialect: Dialect,
+    indent: usize
+}
+
+impl Unparser {
+    pub fn new(dialect: Dialect) -> Self {
+        Self {
+            dialect,
+            indent: 0
+        }
+    }
+
+    pub fn unparse(&mut self, statements: &[Statement]) -> Result<String, Error> {
+        let mut source = String::new();
+
+        for statement in statements {
+            source.push_str(&statement.accept(self)?);
+            source.push('\n');
+        }
+
+        Ok(source)
+    }
+
+    pub fn unparse_expression(&mut self, expression: &Expression) -> Result<String, Error> {
+        self.operand(expression, Precedence::Comma, true)
+    }
+
+    /// Prints an operand in a position that needs at least `context`.
+    /// `rightmost` tells whether nothing of the enclosing expression follows
+    /// it, which matters for ternaries: their last branch extends as far to
+    /// the right as it can.
+    fn operand(&mut self, expression: &Expression, context: Precedence, rightmost: bool) -> Result<String, Error> {
+        let text = expression.accept(self)?;
+        let precedence = precedence(expression);
+
+        if precedence < context || (precedence == Precedence::Ternary && !rightmost) {
+            Ok(format!("({text})"))
+        } else {
+            Ok(text)
+        }
+    }
+
+    /// The binary operators are all left-associative.
+    fn binary(&mut self, left: &Expression, operator: &str, right: &Expression, precedence: Precedence) -> Result<String, Error> {
+        let right_context = match precedence {
+            Precedence::Comma => Precedence::Assignment,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            _ => Precedence::Ternary
+        };
+
+        let left = self.operand(left, precedence, false)?;
+        let right = self.operand(right, right_context, true)?;
+
+        match precedence {
+            Precedence::Comma => Ok(format!("{left}{operator} {right}")),
+            _ => Ok(format!("{left} {operator} {right}"))
+        }
+    }
+
+    fn number(&self, number: f64) -> String {
+        if number.is_nan() {
+            "(0 / 0)".into()
+        } else if number.is_infinite() {
+            // Too large for a double. Reference Lox has no exponents, so
+            // there the only literal that overflows is a long one.
+            let sign = if number < 0.0 { "-" } else { "" };
+
+            match self.dialect.extended_number_literals() {
+                true => format!("{sign}1e999"),
+                false => format!("{sign}1{}", "0".repeat(309))
+            }
+        } else {
+            number.to_string()
+        }
+    }
+
+    /// Text of a string for inside quotes. A literal "${" can only be
+    /// written with an interpolation that gives the "$".
+    fn string_text(&self, string: &str) -> String {
+        match self.dialect.string_interpolation() {
+            true => string.replace("${", "${\"$\"}{"),
+            false => string.to_string()
+        }
+    }
+
+    /// A statement nested in `if` or `while`: blocks stay on the same line,
+    /// anything else goes on its own, indented line.
+    fn branch(&mut self, statement: &Statement) -> Result<String, Error> {
+        if let Statement::Block(_) = statement {
+            return Ok(format!(" {}", statement.accept(self)?));
+        }
+
+        self.indent += 1;
+        let text = statement.accept(self);
+        self.indent -= 1;
+
+        Ok(format!("\n{}{}", INDENT.repeat(self.indent + 1), text?))
+    }
+
+    /// Like `branch`, but always in braces, for a `then` branch that would
+    /// otherwise take the following `else` for itself.
+    fn braced(&mut self, statement: &Statement) -> Result<String, Error> {
+        self.indent += 1;
+        let text = statement.accept(self);
+        self.indent -= 1;
+
+        Ok(format!(" {{\n{}{}\n{}}}", INDENT.repeat(self.indent + 1), text?, INDENT.repeat(self.indent)))
+    }
+}
+
+fn precedence(expression: &Expression) -> Precedence {
+    match expression {
+        Expression::Binary(binary) => operator_precedence(binary.operator().token_type()),
+        Expression::Logical(logical) => operator_precedence(logical.operator().token_type()),
+        Expression::Assign(_) => Precedence::Assignment,
+        Expression::Ternary(_) => Precedence::Ternary,
+        Expression::Unary(_) => Precedence::Unary,
+        Expression::MethodCall(_) | Expression::Call(_) => Precedence::Call,
+        Expression::Literal(literal) => match literal.value() {
+            Value::Number(number) if number.is_sign_negative() && !number.is_nan() => Precedence::Unary,
+            _ => Precedence::Primary
+        },
+        Expression::Grouping(_) | Expression::Variable(_) | Expression::Interpolation(_) => Precedence::Primary
+    }
+}
+
+fn operator_precedence(operator: TokenType) -> Precedence {
+    use TokenType::*;
+
+    match operator {
+        Comma => Precedence::Comma,
+        Or => Precedence::Or,
+        And => Precedence::And,
+        EqualEqual | BangEqual => Precedence::Equality,
+        Greater | GreaterEqual | Less | LessEqual => Precedence::Comparison,
+        Plus | Minus => Precedence::Term,
+        _ => Precedence::Factor
+    }
+}
+
+/// Whether an `else` printed after `statement` would be parsed as part of
+/// it.
+fn dangles(statement: &Statement) -> bool {
+    match statement {
+        Statement::If(statement) => statement.else_branch().is_none_or(dangles),
+        Statement::While(statement) => dangles(statement.body()),
+        _ => false
+    }
+}
+
+impl ExpressionVisitor<Result<String, Error>> for Unparser {
+    fn visit_binary(&mut self, expression: &Binary) -> Result<String, Error> {
+        let operator = expression.operator();
+        self.binary(expression.left(), &operator.to_string(), expression.right(), operator_precedence(operator.token_type()))
+    }
+
+    fn visit_grouping(&mut self, expression: &Grouping) -> Result<String, Error> {
+        Ok(format!("({})", self.operand(expression.expression(), Precedence::Comma, true)?))
+    }
+
+    fn visit_literal(&mut self, expression: &Literal) -> Result<String, Error> {
+        let text = match expression.value() {
+            Value::True => "true".into(),
+            Value::False => "false".into(),
+            Value::Null => self.dialect.null_keyword().into(),
+            Value::Number(number) => self.number(*number),
+            Value::String(string) => format!("\"{}\"", self.string_text(string)),
+            // Lists have no literal syntax. The optimizer does not fold the
+            // method calls that make them, but other trees may hold one.
+            Value::List(_) => return Err(
+                Error::new(
+                    ErrorKind::CompileError {
+                        message: "Cannot unparse a list value".into()
+                    }
+                )
+            )
+        };
+
+        Ok(text)
+    }
+
+    fn visit_unary(&mut self, expression: &Unary) -> Result<String, Error> {
+        Ok(format!("{}{}", expression.operator(), self.operand(expression.right(), Precedence::Unary, true)?))
+    }
+
+    fn visit_ternary(&mut self, expression: &Ternary) -> Result<String, Error> {
+        let first = self.operand(expression.first(), Precedence::Unary, false)?;
+        let second = self.operand(expression.second(), Precedence::Comma, true)?;
+        let third = self.operand(expression.third(), Precedence::Comma, true)?;
+
+        Ok(format!("{first} ? {second} : {third}"))
+    }
+
+    fn visit_variable(&mut self, expression: &Variable) -> Result<String, Error> {
+        Ok(expression.name().to_string())
+    }
+
+    fn visit_assign(&mut self, expression: &Assign) -> Result<String, Error> {
+        Ok(format!("{} = {}", expression.name(), self.operand(expression.value(), Precedence::Assignment, true)?))
+    }
+
+    fn visit_logical(&mut self, expression: &Logical) -> Result<String, Error> {
+        let operator = expression.operator();
+        self.binary(expression.left(), &operator.to_string(), expression.right(), operator_precedence(operator.token_type()))
+    }
+
+    fn visit_interpolation(&mut self, expression: &Interpolation) -> Result<String, Error> {
+        let parts = expression.parts();
+        let mut text = String::from("\"");
+        let mut after_text = false;
+
+        // String parts are written as plain text where that cannot merge them
+        // with a neighbour or turn the whole into a plain string; anywhere
+        // else they are interpolated string literals, which parse the same.
+        for part in parts {
+            match part {
+                Expression::Literal(literal) if parts.len() > 1 && !after_text => match literal.value() {
+                    Value::String(string) if !string.is_empty() => {
+                        text.push_str(&self.string_text(string));
+                        after_text = true;
+                        continue;
+                    },
+                    _ => {}
+                },
+                _ => {}
+            }
+
+            text.push_str(&format!("${{{}}}", self.operand(part, Precedence::Comma, true)?));
+            after_text = false;
+        }
+
+        text.push('"');
+        Ok(text)
+    }
+
+    fn visit_method_call(&mut self, expression: &MethodCall) -> Result<String, Error> {
+        let object = self.operand(expression.object(), Precedence::Call, false)?;
+
+        let arguments: Vec<String> = expression.arguments()
+            .iter()
+            .map(|argument| self.operand(argument, Precedence::Assignment, true))
+            .collect::<Result<_, _>>()?;
+
+        Ok(format!("{object}.{}({})", expression.name(), arguments.join(", ")))
+    }
+
+    fn visit_call(&mut self, expression: &Call) -> Result<String, Error> {
+        let arguments: Vec<String> = expression.arguments()
+            .iter()
+            .map(|argument| self.operand(argument, Precedence::Assignment, true))
+            .collect::<Result<_, _>>()?;
+
+        Ok(format!("{}({})", expression.name(), arguments.join(", ")))
+    }
+}
+
+impl StatementVisitor<Result<String, Error>> for Unparser {
+    fn visit_expression_statement(&mut self, statement: &ExpressionStatement) -> Result<String, Error> {
+        Ok(format!("{};", self.unparse_expression(statement.expression())?))
+    }
+
+    fn visit_print(&mut self, statement: &Print) -> Result<String, Error> {
+        Ok(format!("print {};", self.unparse_expression(statement.expression())?))
+    }
+
+    fn visit_var(&mut self, statement: &Var) -> Result<String, Error> {
+        match statement.right() {
+            Expression::Literal(literal) if literal.value().is_null() => Ok(format!("var {};", statement.name())),
+            right => Ok(format!("var {} = {};", statement.name(), self.unparse_expression(right)?))
+        }
+    }
+
+    fn visit_block(&mut self, statement: &Block) -> Result<String, Error> {
+        if statement.statements().is_empty() {
+            return Ok("{}".into());
+        }
+
+        let mut text = String::from("{\n");
+        self.indent += 1;
+
+        let result = statement.statements().iter().try_for_each(|statement| {
+            text.push_str(&INDENT.repeat(self.indent));
+            text.push_str(&statement.accept(self)?);
+            text.push('\n');
+
+            Ok(())
+        });
+
+        self.indent -= 1;
+        result?;
+        text.push_str(&INDENT.repeat(self.indent));
+        text.push('}');
+
+        Ok(text)
+    }
+
+    fn visit_if(&mut self, statement: &If) -> Result<String, Error> {
+        let mut text = format!("if ({})", self.unparse_expression(statement.condition())?);
+        let then_branch = statement.then_branch();
+
+        let Some(else_branch) = statement.else_branch() else {
+            text.push_str(&self.branch(then_branch)?);
+            return Ok(text);
+        };
+
+        let braced = match then_branch {
+            Statement::Block(_) => {
+                text.push_str(&self.branch(then_branch)?);
+                true
+            },
+            _ if dangles(then_branch) => {
+                text.push_str(&self.braced(then_branch)?);
+                true
+            },
+            _ => {
+                text.push_str(&self.branch(then_branch)?);
+                false
+            }
+        };
+
+        if braced {
+            text.push_str(" else");
+        } else {
+            text.push_str(&format!("\n{}else", INDENT.repeat(self.indent)));
+        }
+
+        match else_branch {
+            Statement::If(_) => text.push_str(&format!(" {}", else_branch.accept(self)?)),
+            _ => text.push_str(&self.branch(else_branch)?)
+        }
+
+        Ok(text)
+    }
+
+    fn visit_while(&mut self, statement: &While) -> Result<String, Error> {
+        let condition = self.unparse_expression(statement.condition())?;
+        Ok(format!("while ({condition}){}", self.branch(statement.body())?))
+    }
+}
@@ -2,4 +2,24 @@ use std::error;
 
 pub fn parse_number(number_str: &str) -> Result<f64, Box<dyn error::Error>> {
     Ok(number_str.parse::<f64>()?)
-}
\ No newline at end of file
+}
+
+pub fn parse_number_literal(literal: &str) -> Result<f64, Box<dyn error::Error>> {
+    let digits = literal.replace('_', "");
+
+    let radix = match digits.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0o" | "0O") => 8,
+        Some("0b" | "0B") => 2,
+        _ => return parse_number(&digits)
+    };
+
+    let mut number = 0.0_f64;
+
+    for c in digits[2..].chars() {
+        let digit = c.to_digit(radix).ok_or_else(|| format!("invalid digit \"{c}\" in number literal"))?;
+        number = number * radix as f64 + digit as f64;
+    }
+
+    Ok(number)
+}
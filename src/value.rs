@@ -1,17 +1,23 @@
-use std::fmt;
+use std::{fmt, rc::Rc};
 
 use super::{
     utils::parse_number,
+    heap::List,
     error::{Error, ErrorKind}
 };
 
-#[derive(Debug, Clone)]
+/// Longest string, in bytes, that operations may create, so that scripts
+/// cannot make the host allocate without bound even without a memory limit.
+pub const MAX_STRING_LENGTH: usize = 1 << 28;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     True,
     False,
     Null,
-    String(String),
-    Number(f64)
+    String(Rc<str>),
+    Number(f64),
+    List(Rc<List>)
 }
 
 impl Value {
@@ -25,13 +31,18 @@ impl Value {
             String(str) => {
                 match parse_number(str) {
                     Ok(value) => Ok(Number(value)),
-                    Err(err) => return Err(
+                    Err(err) => Err(
                         Error::new(
                             ErrorKind::RuntimeError { message: err.to_string() }
                         )
                     )
                 }
-            }
+            },
+            List(_) => Err(
+                Error::new(
+                    ErrorKind::RuntimeError { message: "Cannot convert list to number".into() }
+                )
+            )
         }
     }
 
@@ -54,6 +65,13 @@ impl Value {
                 } else {
                     True
                 }
+            },
+            List(list) => {
+                if list.is_empty() {
+                    False
+                } else {
+                    True
+                }
             }
         }
     }
@@ -65,35 +83,24 @@ impl Value {
             True => "true".into(),
             False => "false".into(),
             Null => "".into(),
-            Number(number) => format!("{}", number),
-            String(str) => str.clone()
+            Number(number) => format!("{}", number).into(),
+            String(str) => str.clone(),
+            List(_) => self.to_string().into()
         };
 
         Value::String(value)
     }
 
     pub fn is_null(&self) -> bool {
-        if let Value::Null = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Value::Null)
     }
 
     pub fn is_true(&self) -> bool {
-        if let Value::True = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Value::True)
     }
 
     pub fn is_false(&self) -> bool {
-        if let Value::False = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Value::False)
     }
 
     pub fn subtract(&self, rhs: &Value) -> Result<Value, Error> {
@@ -137,10 +144,18 @@ impl Value {
 
         match self {
             String(lhs) => match rhs.as_string() {
-                String(rhs) => Ok(String(format!("{}{}", lhs, rhs))),
+                String(rhs) => {
+                    check_string_length(Some(lhs.len() + rhs.len()))?;
+
+                    let mut value = std::string::String::with_capacity(lhs.len() + rhs.len());
+                    value.push_str(lhs);
+                    value.push_str(&rhs);
+
+                    Ok(String(value.into()))
+                },
                 _ => unreachable!()
             },
-            lhs @ _ => match lhs.as_number()? {
+            lhs => match lhs.as_number()? {
                 Number(lhs) => match rhs.as_number()? {
                     Number(rhs) => Ok(Number(lhs + rhs)),
                     _ => unreachable!()
@@ -234,9 +249,30 @@ impl Value {
                 _ => unreachable!()
             },
             String(lhs) => match rhs.as_string() {
-                String(rhs) => if lhs.as_str() == rhs.as_str() { Ok(True) } else { Ok(False) },
+                String(rhs) => if Rc::ptr_eq(lhs, &rhs) || *lhs == rhs { Ok(True) } else { Ok(False) },
                 _ => unreachable!()
-            }
+            },
+            List(_) => Ok(Value::from(self.identical(rhs)))
+        }
+    }
+
+    pub fn identical(&self, rhs: &Value) -> bool {
+        use Value::*;
+
+        match (self, rhs) {
+            (True, True) | (False, False) | (Null, Null) => true,
+            (Number(lhs), Number(rhs)) => lhs == rhs,
+            (String(lhs), String(rhs)) => Rc::ptr_eq(lhs, rhs) || lhs == rhs,
+            (List(lhs), List(rhs)) => {
+                if Rc::ptr_eq(lhs, rhs) {
+                    return true;
+                }
+
+                let (lhs, rhs) = (lhs.items(), rhs.items());
+
+                lhs.len() == rhs.len() && lhs.iter().zip(rhs.iter()).all(|(lhs, rhs)| lhs.identical(rhs))
+            },
+            _ => false
         }
     }
 
@@ -245,12 +281,34 @@ impl Value {
 
         match self.equal(rhs)? {
             True => Ok(False),
-            False => Ok(False),
+            False => Ok(True),
             _ => unreachable!()
         }
     }
 }
 
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        if value {
+            Value::True
+        } else {
+            Value::False
+        }
+    }
+}
+
+impl From<std::string::String> for Value {
+    fn from(value: std::string::String) -> Self {
+        Value::String(value.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.into())
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Value::*;
@@ -260,7 +318,33 @@ impl fmt::Display for Value {
             False => write!(f, "false"),
             Null => write!(f, "null"),
             String(value) => write!(f, "{}", value),
-            Number(value) => write!(f, "{}", value)
+            Number(value) => write!(f, "{}", value),
+            List(list) => {
+                write!(f, "[")?;
+
+                for (index, item) in list.items().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", item)?;
+                }
+
+                write!(f, "]")
+            }
         }
     }
 }
+
+/// Fails if a string of `length` bytes, or `None` for an overflowing
+/// length, would be longer than `MAX_STRING_LENGTH`.
+pub(super) fn check_string_length(length: Option<usize>) -> Result<(), Error> {
+    match length {
+        Some(length) if length <= MAX_STRING_LENGTH => Ok(()),
+        _ => Err(
+            Error::new(
+                ErrorKind::RuntimeError { message: format!("String would be longer than the maximum of {MAX_STRING_LENGTH} bytes") }
+            )
+        )
+    }
+}
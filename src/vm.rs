@@ -0,0 +1,322 @@
+use std::io;
+
+use super::{
+    dialect::Dialect,
+    scanner::Scanner,
+    error::Error,
+    parser::{self, Parser},
+    value::Value,
+    token::TokenType,
+    chunk::{Chunk, OpCode},
+    compiler::Compiler,
+    environment::Environment,
+    heap::{Heap, GcStats},
+    limits::{Budget, Limits, CancelHandle},
+    capabilities::Capabilities,
+    host::Host,
+    output::Output,
+    resolver::Resolver,
+    optimizer::Optimizer,
+    operations,
+    natives
+};
+
+/// Stack machine executing chunks produced by the `Compiler`. Behaves exactly
+/// like the tree-walking `Interpreter`; globals persist between `run` calls.
+#[derive(Debug)]
+pub struct Vm {
+    dialect: Dialect,
+    optimize: bool,
+    max_depth: usize,
+    globals: Environment,
+    stack: Vec<Value>,
+    heap: Heap,
+    budget: Budget,
+    capabilities: Capabilities,
+    host: Host,
+    output: Output
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Self::with_dialect(Dialect::default())
+    }
+
+    pub fn with_dialect(dialect: Dialect) -> Self {
+        Self {
+            dialect,
+            optimize: false,
+            max_depth: parser::MAX_DEPTH,
+            globals: Environment::new(),
+            stack: Vec::new(),
+            heap: Heap::new(),
+            budget: Budget::default(),
+            capabilities: Capabilities::none(),
+            host: Host::system(),
+            output: Output::stdout()
+        }
+    }
+
+    /// Enables constant folding and dead branch pruning before execution.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    /// Limits how deeply programs may nest when parsing. Execution itself
+    /// does not recurse, but parsing and compiling do and need the stack
+    /// described at `Interpreter::set_max_depth`. Defaults to
+    /// `parser::MAX_DEPTH`.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.heap.set_max_memory(limits.max_memory());
+        self.budget.set_limits(limits);
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.budget.limits()
+    }
+
+    /// Handle that stops the current or next run from another thread.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.budget.cancel_handle()
+    }
+
+    /// Host facilities scripts may use. None are granted by default.
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Makes time and randomness reproducible from `seed`, or goes back to
+    /// the system clock and entropy with `None`.
+    pub fn set_deterministic(&mut self, seed: Option<u64>) {
+        self.host = seed.map_or_else(Host::system, Host::deterministic);
+    }
+
+    pub fn host(&mut self) -> &mut Host {
+        &mut self.host
+    }
+
+    /// Sends what `print` writes to `output` instead of standard output.
+    pub fn set_output(&mut self, output: Box<dyn io::Write>) {
+        self.output = Output::new(output);
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    /// Runs a full garbage collection now and returns how many objects it
+    /// freed.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.heap.collect()
+    }
+
+    pub fn compile(&self, code: &str) -> Result<Chunk, Error> {
+        let scanner = Scanner::new(code, self.dialect);
+        let mut tokens = scanner.tokens();
+        let mut parser = Parser::with_dialect(code, &mut tokens, self.dialect);
+        parser.set_max_depth(self.max_depth);
+        let mut statements = parser.parse()?;
+
+        if self.optimize {
+            statements = Optimizer::new(self.dialect).optimize(&statements);
+        }
+
+        let statements = Resolver::new().resolve(&statements);
+
+        Compiler::new().compile(&statements)
+    }
+
+    pub fn run(&mut self, code: &str) -> Result<(), Error> {
+        let chunk = self.compile(code)?;
+
+        self.execute(&chunk)
+    }
+
+    pub fn execute(&mut self, chunk: &Chunk) -> Result<(), Error> {
+        self.budget.start();
+        let result = self.dispatch(chunk);
+        self.stack.clear();
+
+        result
+    }
+
+    fn dispatch(&mut self, chunk: &Chunk) -> Result<(), Error> {
+        let mut ip = 0;
+
+        // `ip` is already past the failing instruction.
+        self.dispatch_from(chunk, &mut ip).map_err(|err| match chunk.location(ip.saturating_sub(1)) {
+            Some((span, line)) => err.at(span, line),
+            None => err
+        })
+    }
+
+    fn dispatch_from(&mut self, chunk: &Chunk, ip: &mut usize) -> Result<(), Error> {
+        let code = chunk.code();
+
+        while *ip < code.len() {
+            self.budget.step()?;
+
+            let op_code = OpCode::from_byte(code[*ip]).expect("invalid opcode");
+            let operand = *ip + 1;
+            *ip = operand + op_code.operand_size();
+
+            match op_code {
+                OpCode::Constant => {
+                    let index = chunk.read_u16(operand) as usize;
+                    self.stack.push(chunk.constant(index).clone());
+                },
+                OpCode::Null => self.stack.push(Value::Null),
+                OpCode::True => self.stack.push(Value::True),
+                OpCode::False => self.stack.push(Value::False),
+                OpCode::Pop => {
+                    self.pop();
+                },
+                OpCode::PopN => {
+                    let count = chunk.read_u16(operand) as usize;
+                    self.stack.truncate(self.stack.len() - count);
+                },
+                OpCode::DefineGlobal => {
+                    let name = constant_name(chunk, operand).clone();
+                    let value = self.pop();
+                    self.globals.define(name, value);
+                },
+                OpCode::GetGlobal => {
+                    let name = constant_name(chunk, operand);
+                    let value = self.globals.get(name)?.clone();
+                    self.stack.push(value);
+                },
+                OpCode::SetGlobal => {
+                    let name = constant_name(chunk, operand);
+                    let value = self.peek().clone();
+                    self.globals.assign(name, value)?;
+                },
+                OpCode::GetLocal => {
+                    let index = chunk.read_u16(operand) as usize;
+                    self.stack.push(self.stack[index].clone());
+                },
+                OpCode::SetLocal => {
+                    let index = chunk.read_u16(operand) as usize;
+                    self.stack[index] = self.peek().clone();
+                },
+                OpCode::Add => self.binary(TokenType::Plus)?,
+                OpCode::Subtract => self.binary(TokenType::Minus)?,
+                OpCode::Multiply => self.binary(TokenType::Star)?,
+                OpCode::Divide => self.binary(TokenType::Slash)?,
+                OpCode::Greater => self.binary(TokenType::Greater)?,
+                OpCode::GreaterEqual => self.binary(TokenType::GreaterEqual)?,
+                OpCode::Less => self.binary(TokenType::Less)?,
+                OpCode::LessEqual => self.binary(TokenType::LessEqual)?,
+                OpCode::Equal => self.binary(TokenType::EqualEqual)?,
+                OpCode::NotEqual => self.binary(TokenType::BangEqual)?,
+                OpCode::Negate => {
+                    let value = self.pop();
+                    self.stack.push(operations::negate(self.dialect, value)?);
+                },
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::from(!operations::is_truthy(self.dialect, &value)));
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    self.output.print(&operations::stringify(self.dialect, &value))?;
+                },
+                OpCode::Jump => {
+                    *ip += chunk.read_u16(operand) as usize;
+                },
+                OpCode::JumpIfFalse => {
+                    if !operations::is_truthy(self.dialect, self.peek()) {
+                        *ip += chunk.read_u16(operand) as usize;
+                    }
+                },
+                OpCode::JumpIfTrue => {
+                    if operations::is_truthy(self.dialect, self.peek()) {
+                        *ip += chunk.read_u16(operand) as usize;
+                    }
+                },
+                OpCode::Loop => {
+                    *ip -= chunk.read_u16(operand) as usize;
+                },
+                OpCode::Interpolate => {
+                    let count = chunk.read_u16(operand) as usize;
+                    let start = self.stack.len() - count;
+                    let value = operations::interpolate(&self.stack[start..])?;
+                    self.stack.truncate(start);
+                    self.push_allocated(value)?;
+                },
+                OpCode::Invoke => {
+                    let name = constant_name(chunk, operand);
+                    let count = code[operand + 2] as usize;
+                    let start = self.stack.len() - count;
+                    let value = operations::invoke(self.dialect, &mut self.heap, &self.stack[start - 1], name, &self.stack[start..])?;
+                    self.stack.truncate(start - 1);
+                    self.push_allocated(value)?;
+
+                    if self.heap.should_collect() {
+                        self.collect_garbage();
+                    }
+                },
+                OpCode::CallNative => {
+                    let name = constant_name(chunk, operand);
+                    let count = code[operand + 2] as usize;
+                    let start = self.stack.len() - count;
+                    let value = natives::call_native(self.capabilities, &mut self.host, name, &self.stack[start..])?;
+                    self.stack.truncate(start);
+                    self.push_allocated(value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn binary(&mut self, operator: TokenType) -> Result<(), Error> {
+        let right = self.pop();
+        let left = self.pop();
+        let value = operations::binary(self.dialect, operator, left, right)?;
+
+        self.push_allocated(value)
+    }
+
+    /// Pushes a value created by an operation, checking it against the
+    /// memory limit first.
+    fn push_allocated(&mut self, value: Value) -> Result<(), Error> {
+        let within_limit = self.heap.charge(&value);
+        self.stack.push(value);
+
+        if !within_limit {
+            self.heap.measure(self.globals.values().chain(self.stack.iter()))?;
+        }
+
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("stack underflow")
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The name in the string constant an instruction refers to.
+fn constant_name(chunk: &Chunk, operand: usize) -> &std::rc::Rc<str> {
+    match chunk.constant(chunk.read_u16(operand) as usize) {
+        Value::String(name) => name,
+        _ => unreachable!()
+    }
+}
@@ -0,0 +1,62 @@
+//! Compiled chunks store each constant once and map code back to the lines
+//! it came from.
+
+use rust_tree_walk::{
+    Dialect,
+    Scanner,
+    chunk::{Chunk, OpCode},
+    compiler::Compiler,
+    parser::Parser,
+    resolver::Resolver,
+    value::Value
+};
+
+fn compile(code: &str) -> Chunk {
+    let scanner = Scanner::new(code, Dialect::Extended);
+    let mut tokens = scanner.tokens();
+    let statements = Parser::new(code, &mut tokens).parse().unwrap();
+
+    Compiler::new().compile(&Resolver::new().resolve(&statements)).unwrap()
+}
+
+/// Lines of the instructions in `chunk` with the given opcode.
+fn lines(chunk: &Chunk, op_code: OpCode) -> Vec<usize> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    while offset < chunk.code().len() {
+        let current = OpCode::from_byte(chunk.code()[offset]).unwrap();
+
+        if current == op_code {
+            lines.push(chunk.line(offset));
+        }
+
+        offset += 1 + current.operand_size();
+    }
+
+    lines
+}
+
+#[test]
+fn constants_are_stored_once() {
+    let mut chunk = Chunk::new();
+
+    let one = chunk.add_constant(Value::Number(1.0));
+    let name = chunk.add_constant(Value::String("name".into()));
+
+    assert_eq!(chunk.add_constant(Value::Number(1.0)), one);
+    assert_eq!(chunk.add_constant(Value::String("name".into())), name);
+    assert_ne!(chunk.add_constant(Value::Number(-0.0)), chunk.add_constant(Value::Number(0.0)));
+    assert_ne!(chunk.add_constant(Value::String("1".into())), one);
+    assert_eq!(chunk.constants().len(), 5);
+}
+
+#[test]
+fn statements_and_literals_keep_their_lines() {
+    let chunk = compile("var a = 1 + x;\nprint a;\nprint\n  2;\nif (a)\n  print a;\nwhile (false) a;\n");
+
+    assert_eq!(lines(&chunk, OpCode::DefineGlobal), [1]);
+    assert_eq!(lines(&chunk, OpCode::GetGlobal), [1, 2, 5, 6, 7]);
+    assert_eq!(lines(&chunk, OpCode::Print), [2, 4, 6]);
+    assert_eq!(lines(&chunk, OpCode::False), [7]);
+}
@@ -0,0 +1,436 @@
+use rust_tree_walk::Dialect;
+
+const METHODS: [&str; 13] = [
+    "length", "substring", "slice", "split", "join", "upper", "lower",
+    "trim", "find", "replace", "repeat", "get", "missing"
+];
+
+const STRING_CHARS: &[u8] = b"abcxyz ABC019_-+*/.,;:!?(){}[]<>=";
+
+/// Binding strength of the expression forms, from loosest to tightest. An
+/// expression is put in parentheses when its context needs a tighter one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Comma,
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary
+}
+
+impl Precedence {
+    fn next(self) -> Precedence {
+        use Precedence::*;
+
+        match self {
+            Comma => Assignment,
+            Assignment => Or,
+            Or => And,
+            And => Equality,
+            Equality => Comparison,
+            Comparison => Term,
+            Term => Factor,
+            Factor => Unary,
+            Unary => Call,
+            Call | Primary => Primary
+        }
+    }
+}
+
+/// Produces random programs that follow the grammar accepted by `Parser`
+/// for a dialect. Programs are syntactically valid but may fail at run time
+/// or loop forever, so they should be run with `Limits`. The same seed
+/// always gives the same programs.
+#[derive(Debug)]
+pub struct Generator {
+    random: SplitMix64,
+    dialect: Dialect,
+    max_depth: usize,
+    depth: usize,
+    scopes: Vec<Vec<String>>,
+    names: usize
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Self::with_dialect(seed, Dialect::default())
+    }
+
+    pub fn with_dialect(seed: u64, dialect: Dialect) -> Self {
+        Self {
+            random: SplitMix64::new(seed),
+            dialect,
+            max_depth: 6,
+            depth: 0,
+            scopes: Vec::new(),
+            names: 0
+        }
+    }
+
+    /// How deeply statements and expressions may nest.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn program(&mut self) -> String {
+        self.declarations().concat()
+    }
+
+    /// A program as its separate top-level declarations. Running them one by
+    /// one, like a REPL does, keeps an early runtime error from hiding the
+    /// rest of the program.
+    pub fn declarations(&mut self) -> Vec<String> {
+        let mut declarations = Vec::new();
+        self.scopes = vec![Vec::new()];
+        self.names = 0;
+
+        // Declare some variables up front so most programs have something
+        // to work with.
+        for _ in 0..self.below(3) + 1 {
+            let name = self.new_name();
+            let (value, _) = self.literal();
+            let mut output = String::new();
+            let statement = format!("{} {name} = {value};", self.keyword("var"));
+            self.line(0, &statement, &mut output);
+            self.scopes[0].push(name);
+            declarations.push(output);
+        }
+
+        for _ in 0..self.below(8) + 1 {
+            let mut output = String::new();
+            self.declaration(0, &mut output);
+            declarations.push(output);
+        }
+
+        declarations
+    }
+
+    /// A single expression, using only names the caller has to define.
+    pub fn expression(&mut self) -> String {
+        self.scopes = vec![Vec::new()];
+        self.expression_at(Precedence::Comma)
+    }
+
+    fn declaration(&mut self, indent: usize, output: &mut String) {
+        if self.chance(4) {
+            let name = self.new_name();
+            let statement = match self.chance(5) {
+                true => format!("{} {name};", self.keyword("var")),
+                false => format!("{} {name} = {};", self.keyword("var"), self.expression_at(Precedence::Comma))
+            };
+
+            self.line(indent, &statement, output);
+            self.scopes.last_mut().unwrap().push(name);
+        } else {
+            self.statement(indent, output);
+        }
+    }
+
+    fn statement(&mut self, indent: usize, output: &mut String) {
+        self.depth += 1;
+
+        let choice = match self.depth >= self.max_depth {
+            true => self.below(2),
+            false => self.below(7)
+        };
+
+        match choice {
+            0 => {
+                let statement = format!("{} {};", self.keyword("print"), self.expression_at(Precedence::Comma));
+                self.line(indent, &statement, output);
+            },
+            1 => {
+                let statement = format!("{};", self.expression_statement());
+                self.line(indent, &statement, output);
+            },
+            2 => self.block(indent, output),
+            3 => {
+                let header = format!("{} ({})", self.keyword("if"), self.expression_at(Precedence::Comma));
+                self.line(indent, &header, output);
+                self.statement(indent + 1, output);
+
+                if self.chance(2) {
+                    let keyword = self.keyword("else");
+                    self.line(indent, &keyword, output);
+                    self.statement(indent + 1, output);
+                }
+            },
+            4 => {
+                let header = format!("{} ({})", self.keyword("while"), self.expression_at(Precedence::Comma));
+                self.line(indent, &header, output);
+                self.statement(indent + 1, output);
+            },
+            5 => self.for_statement(indent, output),
+            _ => {
+                let statement = format!("{};", self.assignment());
+                self.line(indent, &statement, output);
+            }
+        }
+
+        self.depth -= 1;
+    }
+
+    fn block(&mut self, indent: usize, output: &mut String) {
+        self.line(indent, "{", output);
+        self.scopes.push(Vec::new());
+
+        for _ in 0..self.below(4) {
+            self.declaration(indent + 1, output);
+        }
+
+        self.scopes.pop();
+        self.line(indent, "}", output);
+    }
+
+    fn for_statement(&mut self, indent: usize, output: &mut String) {
+        self.scopes.push(Vec::new());
+
+        let initializer = match self.below(3) {
+            0 => ";".to_string(),
+            1 => format!("{};", self.expression_statement()),
+            _ => {
+                let name = self.new_name();
+                let initializer = format!("{} {name} = {};", self.keyword("var"), self.expression_at(Precedence::Comma));
+                self.scopes.last_mut().unwrap().push(name);
+                initializer
+            }
+        };
+
+        let condition = match self.chance(4) {
+            true => String::new(),
+            false => self.expression_at(Precedence::Comma)
+        };
+
+        let increment = match self.chance(4) {
+            true => String::new(),
+            false => self.assignment()
+        };
+
+        let header = format!("{} ({initializer} {condition}; {increment})", self.keyword("for"));
+        self.line(indent, &header, output);
+        self.statement(indent + 1, output);
+
+        self.scopes.pop();
+    }
+
+    fn expression_statement(&mut self) -> String {
+        match self.chance(2) {
+            true => self.assignment(),
+            false => self.expression_at(Precedence::Comma)
+        }
+    }
+
+    fn assignment(&mut self) -> String {
+        let name = self.name();
+        format!("{name} = {}", self.expression_at(Precedence::Assignment))
+    }
+
+    /// An expression that parses as a single operand wherever `context` is
+    /// expected.
+    fn expression_at(&mut self, context: Precedence) -> String {
+        self.depth += 1;
+
+        let (expression, precedence) = match self.depth >= self.max_depth {
+            true => self.primary(),
+            false => self.compound()
+        };
+
+        self.depth -= 1;
+
+        if precedence < context || self.chance(16) {
+            format!("({expression})")
+        } else {
+            expression
+        }
+    }
+
+    fn compound(&mut self) -> (String, Precedence) {
+        use Precedence::*;
+
+        let extended = self.dialect == Dialect::Extended;
+
+        match self.below(12) {
+            0 if extended => {
+                let left = self.expression_at(Comma);
+                let right = self.expression_at(Assignment);
+                (format!("{left}, {right}"), Comma)
+            },
+            1 => (self.assignment(), Assignment),
+            2 => self.binary(Or, &["or"]),
+            3 => self.binary(And, &["and"]),
+            4 => self.binary(Equality, &["==", "!="]),
+            5 => self.binary(Comparison, &[">", ">=", "<", "<="]),
+            6 => self.binary(Term, &["+", "-"]),
+            7 => self.binary(Factor, &["*", "/"]),
+            // The branches of a ternary extend as far right as they can, so
+            // it is always parenthesized unless it stands alone.
+            8 if extended => {
+                let condition = self.expression_at(Unary);
+                let then_branch = self.expression_at(Comma);
+                let else_branch = self.expression_at(Comma);
+                (format!("{condition} ? {then_branch} : {else_branch}"), Comma)
+            },
+            9 => {
+                let operator = self.pick(&["-", "!"]);
+                (format!("{operator}{}", self.expression_at(Unary)), Unary)
+            },
+            10 if extended => {
+                let receiver = match self.chance(2) {
+                    true => self.string(),
+                    false => self.expression_at(Call)
+                };
+                let method = self.pick(&METHODS);
+                let arguments: Vec<String> = (0..self.below(3))
+                    .map(|_| self.expression_at(Assignment))
+                    .collect();
+
+                (format!("{receiver}.{method}({})", arguments.join(", ")), Call)
+            },
+            11 if extended => (self.interpolation(), Primary),
+            _ => self.primary()
+        }
+    }
+
+    fn binary(&mut self, precedence: Precedence, operators: &[&str]) -> (String, Precedence) {
+        let left = self.expression_at(precedence);
+        let operator = self.pick(operators);
+        let operator = match operator.chars().all(|c| c.is_ascii_alphabetic()) {
+            true => self.keyword(operator),
+            false => operator.to_string()
+        };
+        let right = self.expression_at(precedence.next());
+
+        (format!("{left} {operator} {right}"), precedence)
+    }
+
+    fn primary(&mut self) -> (String, Precedence) {
+        match self.chance(2) {
+            true => (self.name(), Precedence::Primary),
+            false => self.literal()
+        }
+    }
+
+    fn literal(&mut self) -> (String, Precedence) {
+        let literal = match self.below(5) {
+            0 | 1 => self.number(),
+            2 => self.string(),
+            3 => {
+                let literal = self.pick(&["true", "false"]);
+                self.keyword(literal)
+            },
+            _ => self.keyword(self.dialect.null_keyword())
+        };
+
+        (literal, Precedence::Primary)
+    }
+
+    fn number(&mut self) -> String {
+        let extended = self.dialect == Dialect::Extended;
+
+        match self.below(6) {
+            0 => format!("{}.{}", self.below(100), self.below(100)),
+            1 if extended => format!("0x{:X}", self.below(4096)),
+            2 if extended => format!("0b{:b}", self.below(64)),
+            3 if extended => format!("1_{:03}", self.below(1000)),
+            4 if extended => format!("{}e{}", self.below(10), self.below(5)),
+            _ => self.below(10).to_string()
+        }
+    }
+
+    fn string(&mut self) -> String {
+        format!("\"{}\"", self.string_content())
+    }
+
+    fn string_content(&mut self) -> String {
+        (0..self.below(6))
+            .map(|_| STRING_CHARS[self.below(STRING_CHARS.len())] as char)
+            .collect()
+    }
+
+    fn interpolation(&mut self) -> String {
+        let mut output = format!("\"{}", self.string_content());
+
+        for _ in 0..self.below(2) + 1 {
+            let expression = self.expression_at(Precedence::Comma);
+            let content = self.string_content();
+            output.push_str(&format!("${{{expression}}}{content}"));
+        }
+
+        output.push('"');
+        output
+    }
+
+    /// A name in scope most of the time, otherwise one that is undefined.
+    fn name(&mut self) -> String {
+        let count = self.scopes.iter().map(Vec::len).sum();
+
+        if count == 0 || self.chance(50) {
+            return format!("u{}", self.below(3));
+        }
+
+        let index = self.below(count);
+        self.scopes.iter().flatten().nth(index).unwrap().clone()
+    }
+
+    fn new_name(&mut self) -> String {
+        self.names += 1;
+        format!("v{}", self.names)
+    }
+
+    /// Keywords are case-insensitive in the extended dialect.
+    fn keyword(&mut self, keyword: &str) -> String {
+        if self.dialect.case_insensitive_keywords() && self.chance(8) {
+            keyword.to_uppercase()
+        } else {
+            keyword.to_string()
+        }
+    }
+
+    fn line(&self, indent: usize, text: &str, output: &mut String) {
+        output.push_str(&"    ".repeat(indent));
+        output.push_str(text);
+        output.push('\n');
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+
+    fn chance(&mut self, one_in: usize) -> bool {
+        self.below(one_in) == 0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.random.next() % bound as u64) as usize
+    }
+}
+
+/// SplitMix64, small and good enough to pick grammar rules with.
+#[derive(Debug)]
+struct SplitMix64 {
+    state: u64
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self {
+            state: seed
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        value ^ (value >> 31)
+    }
+}
@@ -0,0 +1,129 @@
+//! Helpers shared by the integration tests. Each test binary uses only
+//! some of them.
+#![allow(dead_code)]
+
+pub mod generator;
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex}
+};
+
+use rust_tree_walk::{Backend, Dialect, Executor, Scanner, error::Error, token::TokenType};
+
+use generator::Generator;
+
+const DIALECT: &str = "// dialect: ";
+
+/// `programs` generated programs for each dialect, with the dialect and
+/// seed each one was generated from.
+pub fn generated_programs(programs: u64) -> impl Iterator<Item = (Dialect, u64, String)> {
+    [Dialect::Extended, Dialect::Reference].into_iter().flat_map(move |dialect| {
+        (0..programs).map(move |seed| (dialect, seed, Generator::with_dialect(seed, dialect).program()))
+    })
+}
+
+/// A script under `tests/scripts`.
+pub struct Script {
+    pub path: PathBuf,
+    /// The path relative to the crate, for messages.
+    pub name: String,
+    pub code: String,
+    /// The dialect the script asks for, see `dialect`.
+    pub dialect: Dialect
+}
+
+/// Every `.lox` file under `tests/scripts`, in path order.
+pub fn scripts() -> Vec<Script> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut paths = Vec::new();
+    collect_scripts(&root.join("tests/scripts"), &mut paths);
+    assert!(!paths.is_empty(), "no scripts found");
+
+    paths.into_iter().map(|path| {
+        let code = fs::read_to_string(&path).unwrap();
+
+        Script {
+            name: path.strip_prefix(root).unwrap().display().to_string(),
+            dialect: dialect(&code),
+            path,
+            code
+        }
+    }).collect()
+}
+
+fn collect_scripts(path: &Path, scripts: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = fs::read_dir(path).unwrap().map(|entry| entry.unwrap().path()).collect();
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_scripts(&entry, scripts);
+        } else if entry.extension().is_some_and(|extension| extension == "lox") {
+            scripts.push(entry);
+        }
+    }
+}
+
+/// The dialect a script asks for with a `// dialect: <name>` comment, or
+/// the default one.
+pub fn dialect(code: &str) -> Dialect {
+    line_comments(code, Dialect::Extended)
+        .into_iter()
+        .find_map(|(_, text)| text.strip_prefix(DIALECT).map(|dialect| dialect.trim().parse().unwrap()))
+        .unwrap_or_default()
+}
+
+/// The `//` comments in `code`, as the scanner finds them, with the byte
+/// offset each starts at. Tokens the scanner rejects are skipped.
+pub fn line_comments(code: &str, dialect: Dialect) -> Vec<(usize, &str)> {
+    Scanner::new(code, dialect)
+        .tokens_with_trivia()
+        .filter_map(Result::ok)
+        .filter(|token| token.token_type() == TokenType::Comment)
+        .map(|token| (token.span().start(), token.lexeme(code)))
+        .filter(|(_, text)| text.starts_with("//"))
+        .collect()
+}
+
+/// Collects what a program prints. Clones share the same buffer, so one can
+/// be handed to an executor and the other read afterwards.
+#[derive(Debug, Default, Clone)]
+pub struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    pub fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl io::Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs `code` on a new executor that `configure` has set up, and returns
+/// what it printed along with how the run ended.
+pub fn run_capturing(
+    code: &str,
+    backend: Backend,
+    dialect: Dialect,
+    configure: impl FnOnce(&mut dyn Executor)
+) -> (String, Result<(), Error>) {
+    let capture = Capture::default();
+    let mut executor = backend.executor(dialect);
+    configure(executor.as_mut());
+    executor.set_output(Box::new(capture.clone()));
+
+    let result = executor.run(code);
+
+    (capture.text(), result)
+}
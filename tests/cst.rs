@@ -0,0 +1,87 @@
+//! The syntax tree built in lossless mode must give back the source it was
+//! parsed from byte for byte, and the AST derived from it must be the one
+//! the parser builds directly.
+
+use rust_tree_walk::{
+    Dialect,
+    Scanner,
+    cst::{Element, Node, NodeKind},
+    parser::Parser
+};
+
+mod common;
+
+const PROGRAMS: u64 = 500;
+
+fn parse_tree(code: &str, dialect: Dialect) -> Option<Node> {
+    let scanner = Scanner::new(code, dialect);
+    let mut tokens = scanner.tokens_with_trivia();
+    Parser::with_dialect(code, &mut tokens, dialect).parse_tree().ok()
+}
+
+fn check_lossless(description: &str, code: &str, dialect: Dialect) {
+    let scanner = Scanner::new(code, dialect);
+    let mut tokens = scanner.tokens();
+    let statements = Parser::with_dialect(code, &mut tokens, dialect).parse();
+
+    let Some(tree) = parse_tree(code, dialect) else {
+        assert!(statements.is_err(), "{description}: only the lossless parse failed");
+        return;
+    };
+
+    let text: String = tree.tokens().iter().map(|token| token.lexeme(code)).collect();
+    assert_eq!(text, code, "{description}: tokens do not cover the source");
+    assert_eq!(tree.statements(code, dialect).ok(), statements.ok(), "{description}: derived AST differs");
+}
+
+/// Generated programs have no comments of their own.
+fn with_comments(code: &str) -> String {
+    code.replace('\n', " /* block /* nested */ */ // line\n\t")
+}
+
+#[test]
+fn generated_programs_are_lossless() {
+    for (dialect, seed, code) in common::generated_programs(PROGRAMS) {
+        check_lossless(&format!("seed {seed} ({dialect})"), &code, dialect);
+        check_lossless(&format!("seed {seed} with comments ({dialect})"), &with_comments(&code), dialect);
+    }
+}
+
+#[test]
+fn scripts_are_lossless() {
+    for script in common::scripts() {
+        check_lossless(&script.name, &script.code, script.dialect);
+    }
+}
+
+#[test]
+fn trivia_sits_between_nodes() {
+    let code = "// leading\nfor (var i = 0; i < 2; i = i + 1) /* body */ print i * 2;\n";
+    let tree = parse_tree(code, Dialect::Extended).unwrap();
+
+    assert_eq!(tree.kind(), NodeKind::Program);
+    assert!(matches!(tree.children().first(), Some(Element::Token(token)) if token.lexeme(code) == "// leading"));
+
+    let kinds: Vec<NodeKind> = tree.nodes().map(Node::kind).collect();
+    assert_eq!(kinds, [NodeKind::For]);
+
+    let for_loop = tree.nodes().next().unwrap();
+    assert_eq!(for_loop.text(code), "for (var i = 0; i < 2; i = i + 1) /* body */ print i * 2;");
+
+    let kinds: Vec<NodeKind> = for_loop.nodes().map(Node::kind).collect();
+    assert_eq!(kinds, [NodeKind::Var, NodeKind::Binary, NodeKind::Assign, NodeKind::Print]);
+
+    let print = for_loop.nodes().last().unwrap();
+    assert_eq!(print.text(code), "print i * 2;");
+}
+
+/// Tokens refer to the source by span, so trees only compare equal when
+/// their tokens sit at the same places.
+#[test]
+fn trees_compare_tokens_by_position() {
+    let tree = |code| parse_tree(code, Dialect::Extended).unwrap();
+
+    assert_eq!(tree("var a = 1;"), tree("var a = 1;"));
+    assert_ne!(tree("var a = 1;"), tree("var abc = 123;"));
+    assert_ne!(tree("var a = 1;"), tree(" var a = 1;"));
+}
@@ -0,0 +1,133 @@
+//! Nesting limits: deep programs fail with an error, long flat ones run,
+//! and at the default limit everything fits a default-sized thread stack.
+
+use std::thread;
+
+use rust_tree_walk::{
+    Backend,
+    Dialect,
+    Scanner,
+    formatter::Formatter,
+    parser::{MAX_DEPTH, LINKS_PER_LEVEL},
+    token::TokenType
+};
+
+mod common;
+
+/// Runs `code` on every backend, with and without the optimizer, on a
+/// thread with the default stack size, and returns the output or the error
+/// message of each run.
+fn run(code: String) -> Vec<Result<String, String>> {
+    let worker = thread::spawn(move || {
+        let mut results = Vec::new();
+
+        for backend in [Backend::TreeWalk, Backend::Bytecode] {
+            for optimize in [false, true] {
+                let (output, result) = common::run_capturing(&code, backend, Dialect::Extended, |executor| {
+                    executor.set_optimize(optimize);
+                });
+
+                results.push(result.map(|_| output).map_err(|err| err.to_string()));
+            }
+        }
+
+        if let Err(err) = Formatter::new(Dialect::Extended).format(&code) {
+            results.push(Err(err.to_string()));
+        }
+
+        results
+    });
+
+    worker.join().unwrap()
+}
+
+fn chain(terms: usize) -> String {
+    vec!["1"; terms].join(" + ")
+}
+
+fn nested(levels: usize, inner: &str) -> String {
+    format!("{}{inner}{}", "(".repeat(levels), ")".repeat(levels))
+}
+
+#[test]
+fn long_chains_are_not_nesting() {
+    let links = MAX_DEPTH * LINKS_PER_LEVEL;
+
+    for terms in [300, links + 1] {
+        for result in run(format!("print {};", chain(terms))) {
+            assert_eq!(result, Ok(format!("{terms}\n")));
+        }
+    }
+
+    for result in run(format!("print {};", nested(MAX_DEPTH - 8, &chain(links / 2)))) {
+        assert_eq!(result, Ok(format!("{}\n", links / 2)));
+    }
+
+    for result in run(format!("var a = true; print a and a or {};", vec!["a"; links - 2].join(" and "))) {
+        assert_eq!(result, Ok("true\n".into()));
+    }
+
+    for result in run(format!("print \"a\"{};", ".upper()".repeat(links))) {
+        assert_eq!(result, Ok("A\n".into()));
+    }
+}
+
+#[test]
+fn too_many_chained_operators() {
+    let terms = MAX_DEPTH * LINKS_PER_LEVEL + 2;
+
+    for result in run(format!("print {};", chain(terms))) {
+        let err = result.unwrap_err();
+        assert!(err.contains("Too many chained operators"), "{err}");
+    }
+}
+
+#[test]
+fn too_deeply_nested() {
+    let levels = MAX_DEPTH + 1;
+
+    for code in [
+        format!("print {};", nested(levels, "1")),
+        format!("print {}1;", "-".repeat(levels)),
+        format!("{}{}", "{".repeat(levels), "}".repeat(levels))
+    ] {
+        for result in run(code) {
+            let err = result.unwrap_err();
+            assert!(err.contains("Too deeply nested"), "{err}");
+        }
+    }
+}
+
+#[test]
+fn default_limit_fits_a_default_stack() {
+    for levels in [MAX_DEPTH - 4, MAX_DEPTH, MAX_DEPTH + 1] {
+        for code in [
+            format!("print {};", nested(levels, "1")),
+            format!("print {}1;", "-".repeat(levels)),
+            format!("print {}1{};", "\"${".repeat(levels), "}\"".repeat(levels)),
+            format!("print {}1;", "true ? 1 : ".repeat(levels)),
+            format!("print {}\"a\"{};", "(".repeat(levels), ".upper())".repeat(levels)),
+            format!("{}print 1;{}", "{".repeat(levels), "}".repeat(levels)),
+            format!("{}print 1;", "if (true) ".repeat(levels))
+        ] {
+            for result in run(code.clone()) {
+                match result {
+                    Ok(output) => assert!(output.ends_with("1\n") || output == "A\n", "{code}: {output}"),
+                    Err(err) if levels > MAX_DEPTH - 4 => assert!(err.contains("Too deeply nested"), "{err}"),
+                    Err(err) => panic!("{code}: {err}")
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn deeply_nested_comments() {
+    let levels = 200_000;
+    let code = format!("{}{}print 1;", "/*".repeat(levels), "*/".repeat(levels));
+    let tokens: Vec<_> = Scanner::new(&code, Dialect::Extended).tokens_with_trivia().map(Result::unwrap).collect();
+
+    assert_eq!(tokens[0].token_type(), TokenType::Comment);
+    assert_eq!(tokens[0].span().end(), 4 * levels);
+    assert_eq!(tokens[1].token_type(), TokenType::Print);
+}
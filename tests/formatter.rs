@@ -0,0 +1,142 @@
+//! Formatting must keep the program the same, keep every comment, and be
+//! idempotent.
+
+use rust_tree_walk::{
+    Dialect,
+    Scanner,
+    formatter::Formatter,
+    parser::Parser,
+    token::TokenType,
+    unparser::Unparser
+};
+
+mod common;
+
+const PROGRAMS: u64 = 300;
+
+const COMMENTS: [&str; 4] = [" /* block */ ", "/* tight */", " // line\n", "\n// own line\n\n"];
+
+fn unparse(code: &str, dialect: Dialect) -> String {
+    let scanner = Scanner::new(code, dialect);
+    let mut tokens = scanner.tokens();
+    let statements = Parser::with_dialect(code, &mut tokens, dialect).parse().unwrap();
+
+    Unparser::new(dialect).unparse(&statements).unwrap()
+}
+
+fn parses(code: &str, dialect: Dialect) -> bool {
+    let scanner = Scanner::new(code, dialect);
+    let mut tokens = scanner.tokens();
+    Parser::with_dialect(code, &mut tokens, dialect).parse().is_ok()
+}
+
+fn comments(code: &str, dialect: Dialect) -> Vec<String> {
+    Scanner::new(code, dialect)
+        .tokens_with_trivia()
+        .map(Result::unwrap)
+        .filter(|token| token.token_type() == TokenType::Comment)
+        .map(|token| token.lexeme(code).to_string())
+        .collect()
+}
+
+/// Puts comments after some of the tokens, wherever a comment can go.
+fn with_comments(code: &str, dialect: Dialect, seed: u64) -> String {
+    let mut output = String::new();
+    let mut end = 0;
+
+    for (index, token) in Scanner::new(code, dialect).tokens().map(Result::unwrap).enumerate() {
+        output.push_str(&code[end..token.span().end()]);
+        end = token.span().end();
+
+        // After an interpolation's "}" the string goes on.
+        let choice = ((seed + index as u64 * 7) % 11) as usize;
+
+        if token.token_type() != TokenType::InterpolationEnd && choice < COMMENTS.len() {
+            // "/" and "/*" would run together into "//".
+            if token.token_type() == TokenType::Slash {
+                output.push(' ');
+            }
+
+            output.push_str(COMMENTS[choice]);
+        }
+    }
+
+    output.push_str(&code[end..]);
+    output
+}
+
+fn check_format(description: &str, code: &str, dialect: Dialect) {
+    let formatter = Formatter::new(dialect);
+    let formatted = formatter.format(code).unwrap_or_else(|err| panic!("{description}: {err}\n{code}"));
+
+    assert_eq!(unparse(&formatted, dialect), unparse(code, dialect), "{description}: program changed:\n{formatted}");
+    assert_eq!(comments(&formatted, dialect), comments(code, dialect), "{description}: comments changed:\n{formatted}");
+    assert_eq!(formatter.format(&formatted).unwrap(), formatted, "{description}: formatting is not idempotent");
+}
+
+#[test]
+fn generated_programs_format() {
+    for (dialect, seed, code) in common::generated_programs(PROGRAMS) {
+        check_format(&format!("seed {seed} ({dialect})"), &code, dialect);
+
+        let code = with_comments(&code, dialect, seed);
+        check_format(&format!("seed {seed} with comments ({dialect})"), &code, dialect);
+    }
+}
+
+#[test]
+fn scripts_format() {
+    for script in common::scripts() {
+        if parses(&script.code, script.dialect) {
+            check_format(&script.name, &script.code, script.dialect);
+        }
+    }
+}
+
+#[test]
+fn formats_in_house_style() {
+    let code = "\
+// Counts down.
+VAR n=3 ;   var done;
+
+
+WHILE(n>0){print \"${n   }...\" ;n=n-1;}
+if(n==0)done=true;else{ print \"never\"; }   // unreachable
+for(var i=0;i<2;i=i+1) print i .upper( ) ;
+";
+    let expected = "\
+// Counts down.
+var n = 3;
+var done;
+
+while (n > 0) {
+    print \"${n}...\";
+    n = n - 1;
+}
+if (n == 0)
+    done = true;
+else {
+    print \"never\";
+} // unreachable
+for (var i = 0; i < 2; i = i + 1)
+    print i.upper();
+";
+
+    assert_eq!(Formatter::new(Dialect::Extended).format(code).unwrap(), expected);
+}
+
+#[test]
+fn formats_edge_cases() {
+    let cases = [
+        ("print 1 .upper();", "print 1 .upper();\n"),
+        ("print (1).upper();", "print (1).upper();\n"),
+        ("var x = \"a\" // c\n;", "var x = \"a\"; // c\n"),
+        ("{ print 1 /* b */ // c\n; }", "{\n    print 1; /* b */ // c\n}\n"),
+        ("print 1 /* b */;", "print 1 /* b */;\n")
+    ];
+
+    for (code, expected) in cases {
+        assert_eq!(Formatter::new(Dialect::Extended).format(code).unwrap(), expected, "{code}");
+        check_format(code, code, Dialect::Extended);
+    }
+}
@@ -0,0 +1,168 @@
+//! Runs random input through the whole pipeline and fails on any panic.
+//! Errors are fine; only crashes are bugs here.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use rust_tree_walk::{
+    Backend,
+    Dialect,
+    Limits,
+    Scanner,
+    parser::Parser
+};
+
+mod common;
+
+use common::generator::Generator;
+
+const PROGRAMS: u64 = 2000;
+const INPUTS: u64 = 2000;
+
+const TOKENS: [&str; 40] = [
+    "(", ")", "{", "}", ",", ".", "-", "+", ";", "*", "/", "?", ":", "!", "!=", "=", "==",
+    ">", ">=", "<", "<=", "\"", "\"a${", "}", "x", "y", "1", "0x", "1_", "2.5", "1e", "and",
+    "or", "var", "print", "if", "else", "while", "for", "null"
+];
+
+fn limits() -> Limits {
+    let mut limits = Limits::new();
+    limits.set_max_steps(Some(5_000));
+    limits.set_max_memory(Some(1 << 20));
+    limits
+}
+
+/// The default configuration, which has no memory limit, except for the
+/// steps that make programs that loop forever end. Allocations only the
+/// memory limit would stop get through here.
+fn default_limits() -> Limits {
+    let mut limits = Limits::new();
+    limits.set_max_steps(Some(5_000));
+    limits
+}
+
+fn parses(code: &str, dialect: Dialect) -> bool {
+    let scanner = Scanner::new(code, dialect);
+    let mut tokens = scanner.tokens();
+    Parser::with_dialect(code, &mut tokens, dialect).parse().is_ok()
+}
+
+fn run(code: &str, dialect: Dialect, backend: Backend, optimize: bool, limits: Limits) {
+    let mut executor = backend.executor(dialect);
+    executor.set_optimize(optimize);
+    executor.set_limits(limits);
+    executor.set_output(Box::new(std::io::sink()));
+    let _ = executor.run(code);
+    executor.collect_garbage();
+}
+
+/// Runs `check` and reports the input that made it panic.
+fn check(description: &str, code: &str, check: impl FnOnce()) {
+    if panic::catch_unwind(AssertUnwindSafe(check)).is_err() {
+        panic!("{description} panicked on:\n{code}");
+    }
+}
+
+fn run_everywhere(description: &str, code: &str, dialect: Dialect) {
+    for backend in [Backend::TreeWalk, Backend::Bytecode] {
+        for optimize in [false, true] {
+            check(&format!("{description} ({backend}, optimize: {optimize})"), code, || {
+                run(code, dialect, backend, optimize, limits())
+            });
+        }
+
+        check(&format!("{description} ({backend}, default limits)"), code, || {
+            run(code, dialect, backend, false, default_limits())
+        });
+    }
+}
+
+#[test]
+fn generated_programs_parse_and_run_without_panicking() {
+    for dialect in [Dialect::Extended, Dialect::Reference] {
+        for seed in 0..PROGRAMS {
+            let declarations = Generator::with_dialect(seed, dialect).declarations();
+            let code = declarations.concat();
+            let description = format!("seed {seed} ({dialect})");
+
+            check(&description, &code, || {
+                assert!(parses(&code, dialect), "generated program does not parse");
+            });
+
+            run_everywhere(&description, &code, dialect);
+
+            // One declaration at a time, so statements after a runtime error
+            // still run.
+            for backend in [Backend::TreeWalk, Backend::Bytecode] {
+                check(&format!("{description} ({backend}, declaration by declaration)"), &code, || {
+                    let mut executor = backend.executor(dialect);
+                    executor.set_limits(limits());
+                    executor.set_output(Box::new(std::io::sink()));
+
+                    for declaration in &declarations {
+                        let _ = executor.run(declaration);
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[test]
+fn random_bytes_do_not_panic() {
+    let mut random = Random(1);
+
+    for input in 0..INPUTS {
+        // Mostly ASCII, so that some input gets past the scanner, with
+        // bytes that make multibyte and invalid UTF-8 mixed in.
+        let bytes: Vec<u8> = (0..random.below(64))
+            .map(|_| match random.below(4) {
+                0 => random.below(256) as u8,
+                _ => random.below(128) as u8
+            })
+            .collect();
+        let code = String::from_utf8_lossy(&bytes);
+
+        for dialect in [Dialect::Extended, Dialect::Reference] {
+            let description = format!("input {input} ({dialect})");
+
+            check(&description, &code, || {
+                parses(&code, dialect);
+            });
+
+            run_everywhere(&description, &code, dialect);
+        }
+    }
+}
+
+#[test]
+fn random_token_soup_does_not_panic() {
+    let mut random = Random(2);
+
+    for input in 0..INPUTS {
+        let code: Vec<&str> = (0..random.below(24)).map(|_| TOKENS[random.below(TOKENS.len())]).collect();
+        let code = code.join(" ");
+
+        for dialect in [Dialect::Extended, Dialect::Reference] {
+            let description = format!("input {input} ({dialect})");
+
+            check(&description, &code, || {
+                parses(&code, dialect);
+            });
+
+            run_everywhere(&description, &code, dialect);
+        }
+    }
+}
+
+/// xorshift64*, to keep the harness free of dependencies.
+struct Random(u64);
+
+impl Random {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) % bound.max(1) as u64) as usize
+    }
+}
@@ -0,0 +1,159 @@
+//! Runs every script under `tests/scripts` on both backends and compares
+//! what it prints with the expectations written in its comments:
+//!
+//! - `// expect: <line>` for each line the script prints, in order;
+//! - `// expect runtime error: <message>` when the run fails;
+//! - `// error at line <N>` when scanning or parsing fails on line N;
+//! - `// dialect: reference` to run the script as strict Lox.
+//!
+//! Set `UPDATE_EXPECT=1` to rewrite the expectations from the actual
+//! results of the tree-walking interpreter after an intentional change.
+
+use std::{
+    env,
+    fs,
+    thread
+};
+
+use rust_tree_walk::Backend;
+
+mod common;
+
+const EXPECT: &str = "// expect:";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error:";
+const ERROR_AT_LINE: &str = "// error at line";
+
+/// Deeply nested scripts recurse a lot in debug builds.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Outcome {
+    output: Vec<String>,
+    runtime_error: Option<String>,
+    error_line: Option<usize>
+}
+
+impl Outcome {
+    fn expected(source: &str) -> Self {
+        let mut outcome = Outcome::default();
+
+        for (_, text) in common::line_comments(source, common::dialect(source)) {
+            if let Some(output) = text.strip_prefix(EXPECT) {
+                // An expected empty line may have lost its trailing space.
+                outcome.output.push(output.strip_prefix(' ').unwrap_or(output).to_string());
+            } else if let Some(message) = text.strip_prefix(EXPECT_RUNTIME_ERROR) {
+                outcome.runtime_error = Some(message.trim_start().to_string());
+            } else if let Some(line) = text.strip_prefix(ERROR_AT_LINE) {
+                outcome.error_line = line.trim().parse().ok();
+            }
+        }
+
+        outcome
+    }
+}
+
+fn run(source: &str, backend: Backend) -> Outcome {
+    let (output, result) = common::run_capturing(source, backend, common::dialect(source), |_| {});
+    let mut outcome = Outcome {
+        output: output.lines().map(str::to_string).collect(),
+        ..Outcome::default()
+    };
+
+    match result {
+        Err(err) if err.is_runtime() => outcome.runtime_error = Some(err.message()),
+        Err(err) => outcome.error_line = Some(err.line().unwrap_or(0)),
+        Ok(()) => {}
+    }
+
+    outcome
+}
+
+/// Moves the expectations in `source` to match `actual`, keeping them on the
+/// lines they were written on where possible. Surplus expectations are
+/// removed, missing ones are appended at the end.
+fn rewrite(source: &str, actual: &Outcome) -> String {
+    let mut output = actual.output.iter();
+    let mut runtime_error = actual.runtime_error.as_ref();
+    let mut error_line = actual.error_line;
+    let mut lines = Vec::new();
+    let comments = common::line_comments(source, common::dialect(source));
+    let mut start = 0;
+
+    for line in source.split_inclusive('\n') {
+        let end = start + line.len();
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        let comment = comments.iter().find(|(offset, _)| (start..end).contains(offset));
+        let (code, comment) = match comment {
+            Some((offset, _)) => (&line[..offset - start], Some(&line[offset - start..])),
+            None => (line, None)
+        };
+        start = end;
+
+        let replacement = match comment {
+            Some(comment) if comment.starts_with(EXPECT) => Some(output.next().map(|output| format!("{EXPECT} {output}"))),
+            Some(comment) if comment.starts_with(EXPECT_RUNTIME_ERROR) => {
+                Some(runtime_error.take().map(|message| format!("{EXPECT_RUNTIME_ERROR} {message}")))
+            },
+            Some(comment) if comment.starts_with(ERROR_AT_LINE) => Some(error_line.take().map(|line| format!("{ERROR_AT_LINE} {line}"))),
+            _ => None
+        };
+
+        match replacement {
+            Some(Some(comment)) => lines.push(format!("{code}{comment}")),
+            Some(None) if code.trim().is_empty() => {},
+            Some(None) => lines.push(code.trim_end().to_string()),
+            None => lines.push(line.to_string())
+        }
+    }
+
+    lines.extend(output.map(|output| format!("{EXPECT} {output}")));
+    lines.extend(runtime_error.map(|message| format!("{EXPECT_RUNTIME_ERROR} {message}")));
+    lines.extend(error_line.map(|line| format!("{ERROR_AT_LINE} {line}")));
+
+    lines.join("\n") + "\n"
+}
+
+fn check_scripts() -> Vec<String> {
+    let update = env::var_os("UPDATE_EXPECT").is_some();
+    let mut failures = Vec::new();
+
+    for script in common::scripts() {
+        let mut source = script.code;
+        let name = script.name;
+
+        if update {
+            let actual = run(&source, Backend::TreeWalk);
+
+            if actual != Outcome::expected(&source) {
+                source = rewrite(&source, &actual);
+                fs::write(&script.path, &source).unwrap();
+            }
+        }
+
+        let expected = Outcome::expected(&source);
+
+        for backend in [Backend::TreeWalk, Backend::Bytecode] {
+            let actual = run(&source, backend);
+
+            if actual != expected {
+                failures.push(format!("{name} ({backend}):\n  expected {expected:?}\n  actual   {actual:?}"));
+            }
+        }
+    }
+
+    failures
+}
+
+#[test]
+fn scripts_match_expectations() {
+    let failures = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(check_scripts)
+        .unwrap()
+        .join()
+        .unwrap();
+
+    assert!(failures.is_empty(), "{} script runs failed:\n{}", failures.len(), failures.join("\n"));
+}
@@ -0,0 +1,68 @@
+//! Garbage collection must not touch lists that are still in use.
+
+use rust_tree_walk::{Backend, Dialect, heap::Heap, value::Value};
+
+mod common;
+
+/// Keeps lists in a global, a block local and on the VM stack while the
+/// loop allocates enough to collect many times.
+const PROGRAM: &str = r#"
+var words = "a,b,c".split(",");
+var broken = 0;
+{
+    var local = "x y".split(" ");
+    var i = 0;
+    while (i < 3000) {
+        var temp = "1 2 3".split(" ");
+        if ("1,2".split(",").get("a b".split(" ").length() - 2) != "1") broken = broken + 1;
+        if ("".join(temp) != "123" or "".join(local) != "xy") broken = broken + 1;
+        i = i + 1;
+    }
+    print "+".join(local);
+}
+print broken;
+"#;
+
+#[test]
+fn live_lists_survive_collections() {
+    for backend in [Backend::TreeWalk, Backend::Bytecode] {
+        let output = common::Capture::default();
+        let mut executor = backend.executor(Dialect::Extended);
+        executor.set_output(Box::new(output.clone()));
+
+        executor.run(PROGRAM).unwrap();
+        assert!(executor.gc_stats().collections() > 1, "{backend}: {}", executor.gc_stats());
+
+        executor.collect_garbage();
+        executor.run("print \"-\".join(words);").unwrap();
+
+        assert_eq!(output.text(), "x+y\n0\na-b-c\n", "{backend}");
+    }
+}
+
+#[test]
+fn held_lists_are_roots() {
+    let mut heap = Heap::new();
+    let inner = heap.alloc_list(vec![Value::from("a"), Value::from("b")]);
+    let outer = heap.alloc_list(vec![inner, Value::from("c")]);
+    let held = heap.alloc_list(vec![Value::from("d")]);
+    let dropped = heap.alloc_list(vec![Value::from("e")]);
+    drop(dropped);
+
+    // Whatever a caller holds is live, and so is what it refers to.
+    assert_eq!(heap.collect(), 1);
+    assert_eq!(heap.collect(), 0);
+    assert!(matches!(&held, Value::List(list) if list.len() == 1));
+
+    let Value::List(outer) = outer else { unreachable!() };
+    assert_eq!(outer.len(), 2);
+
+    match &outer.items()[0] {
+        Value::List(inner) => assert_eq!(*inner.items(), [Value::from("a"), Value::from("b")]),
+        value => panic!("{value:?}")
+    };
+
+    drop(outer);
+    assert_eq!(heap.collect(), 2);
+    assert_eq!(heap.stats().live(), 1);
+}
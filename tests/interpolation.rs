@@ -0,0 +1,45 @@
+//! Runtime errors inside `${...}` point at the interpolated expression.
+
+use rust_tree_walk::{Backend, Dialect};
+
+fn error_location(code: &str, backend: Backend, optimize: bool) -> (String, Option<usize>) {
+    let mut executor = backend.executor(Dialect::Extended);
+    executor.set_optimize(optimize);
+    executor.set_output(Box::new(std::io::sink()));
+
+    let err = executor.run(code).unwrap_err();
+    assert!(err.is_runtime(), "{err}");
+
+    let span = err.span().unwrap_or_else(|| panic!("{backend}: {err} has no span"));
+    (code[span.start()..span.end()].to_string(), err.line())
+}
+
+#[test]
+fn errors_point_into_the_literal() {
+    let cases = [
+        ("print \"value: ${missing}\";", "missing", 1),
+        ("var a = 1;\nprint \"${a} and ${a + nope * 2}!\";", "a + nope * 2", 2),
+        // The innermost interpolation is the most precise.
+        ("print \"outer ${\"inner ${1 - missing}\"}\";", "1 - missing", 1),
+        ("print \"first\nsecond ${\n  missing\n}\";", "missing", 3)
+    ];
+
+    for backend in [Backend::TreeWalk, Backend::Bytecode] {
+        for optimize in [false, true] {
+            for (code, text, line) in cases {
+                assert_eq!(error_location(code, backend, optimize), (text.to_string(), Some(line)), "{backend}: {code}");
+            }
+        }
+    }
+}
+
+#[test]
+fn errors_outside_interpolations_have_no_location() {
+    for backend in [Backend::TreeWalk, Backend::Bytecode] {
+        let mut executor = backend.executor(Dialect::Extended);
+        let err = executor.run("print \"${1}\" + missing;").unwrap_err();
+
+        assert_eq!(err.span(), None);
+        assert_eq!(err.line(), None);
+    }
+}
@@ -0,0 +1,212 @@
+//! Scripted exchanges with the language server, framed as an editor would
+//! send them.
+
+use std::io;
+
+use serde_json::{json, Value};
+
+use rust_tree_walk::{
+    Dialect,
+    lsp::{self, Server}
+};
+
+const URI: &str = "file:///tmp/count.lox";
+
+const CODE: &str = "\
+var total = 0;
+{
+    var total = 1;
+    print total;
+}
+total = total + 1;
+print total;
+";
+
+fn exchange(messages: &[Value]) -> (Server, Vec<Value>) {
+    let mut input = Vec::new();
+
+    for message in messages {
+        lsp::write_message(&mut input, message).unwrap();
+    }
+
+    let mut server = Server::new(Dialect::Extended);
+    let mut output = Vec::new();
+    server.run(&input[..], &mut output).unwrap();
+
+    let mut reader = &output[..];
+    let mut replies = Vec::new();
+
+    while let Some(body) = lsp::read_message(&mut reader).unwrap() {
+        replies.push(serde_json::from_str(&body).unwrap());
+    }
+
+    (server, replies)
+}
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn open(text: &str) -> Value {
+    notification("textDocument/didOpen", json!({
+        "textDocument": { "uri": URI, "languageId": "lox", "version": 1, "text": text }
+    }))
+}
+
+fn at(line: u64, character: u64) -> Value {
+    json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+}
+
+fn range(start: (u64, u64), end: (u64, u64)) -> Value {
+    json!({
+        "start": { "line": start.0, "character": start.1 },
+        "end": { "line": end.0, "character": end.1 }
+    })
+}
+
+fn result(replies: &[Value], id: u64) -> &Value {
+    let reply = replies.iter().find(|reply| reply["id"] == id).unwrap();
+    &reply["result"]
+}
+
+#[test]
+fn session_lifecycle() {
+    let (server, replies) = exchange(&[
+        request(1, "initialize", json!({ "capabilities": {} })),
+        notification("initialized", json!({})),
+        request(2, "textDocument/formatting", json!({})),
+        request(3, "shutdown", Value::Null),
+        notification("exit", Value::Null),
+        // Never read.
+        request(4, "shutdown", Value::Null)
+    ]);
+
+    assert!(server.is_shut_down());
+    assert_eq!(replies.len(), 3);
+
+    let capabilities = &result(&replies, 1)["capabilities"];
+    assert_eq!(capabilities["textDocumentSync"], 1);
+    assert_eq!(capabilities["definitionProvider"], true);
+
+    assert_eq!(replies[1]["error"]["code"], -32601);
+    assert_eq!(replies[2], json!({ "jsonrpc": "2.0", "id": 3, "result": null }));
+}
+
+#[test]
+fn diagnostics_follow_changes() {
+    let (server, replies) = exchange(&[
+        open("var a = 1;\nprint a +;\n"),
+        notification("textDocument/didChange", json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "print \"open;\n" }]
+        })),
+        notification("textDocument/didChange", json!({
+            "textDocument": { "uri": URI, "version": 3 },
+            "contentChanges": [{ "text": CODE }]
+        })),
+        notification("textDocument/didClose", json!({ "textDocument": { "uri": URI } }))
+    ]);
+
+    // The client left without shutting down.
+    assert!(!server.is_shut_down());
+    assert!(replies.iter().all(|reply| reply["method"] == "textDocument/publishDiagnostics"));
+
+    let parser_error = &replies[0]["params"];
+    assert_eq!(parser_error["version"], 1);
+    assert_eq!(parser_error["diagnostics"][0]["range"], range((1, 9), (1, 10)));
+    assert!(parser_error["diagnostics"][0]["message"].as_str().unwrap().contains("\";\""));
+
+    let scanner_error = &replies[1]["params"];
+    assert_eq!(scanner_error["version"], 2);
+    assert_eq!(scanner_error["diagnostics"][0]["range"], range((0, 6), (1, 0)));
+    assert!(scanner_error["diagnostics"][0]["message"].as_str().unwrap().contains("Unterminated string"));
+
+    assert_eq!(replies[2]["params"]["diagnostics"], json!([]));
+    assert_eq!(replies[3]["params"], json!({ "uri": URI, "diagnostics": [] }));
+}
+
+#[test]
+fn navigation_follows_scopes() {
+    let (_, replies) = exchange(&[
+        open(CODE),
+        request(1, "textDocument/documentSymbol", json!({ "textDocument": { "uri": URI } })),
+        // "total" printed in the block.
+        request(2, "textDocument/definition", at(3, 11)),
+        // "total" on the right of the assignment.
+        request(3, "textDocument/definition", at(5, 9)),
+        request(4, "textDocument/references", json!({
+            "textDocument": { "uri": URI },
+            "position": { "line": 0, "character": 5 },
+            "context": { "includeDeclaration": true }
+        })),
+        request(5, "textDocument/hover", at(3, 11)),
+        request(6, "textDocument/hover", at(4, 0)),
+        request(7, "textDocument/definition", json!({ "textDocument": { "uri": "file:///other.lox" }, "position": {} }))
+    ]);
+
+    assert_eq!(result(&replies, 1), &json!([{
+        "name": "total",
+        "kind": 13,
+        "range": range((0, 0), (0, 14)),
+        "selectionRange": range((0, 4), (0, 9))
+    }]));
+
+    assert_eq!(result(&replies, 2), &json!({ "uri": URI, "range": range((2, 8), (2, 13)) }));
+    assert_eq!(result(&replies, 3), &json!({ "uri": URI, "range": range((0, 4), (0, 9)) }));
+
+    let references: Vec<&Value> = result(&replies, 4).as_array().unwrap().iter().map(|location| &location["range"]).collect();
+    assert_eq!(references, [
+        &range((0, 4), (0, 9)),
+        &range((5, 0), (5, 5)),
+        &range((5, 8), (5, 13)),
+        &range((6, 6), (6, 11))
+    ]);
+
+    assert_eq!(
+        result(&replies, 5)["contents"]["value"],
+        "```lox\nvar total = 1;\n```\nLocal variable declared on line 3"
+    );
+    assert_eq!(result(&replies, 6), &Value::Null);
+
+    let error = &replies.iter().find(|reply| reply["id"] == 7).unwrap()["error"];
+    assert_eq!(error["code"], -32602);
+}
+
+#[test]
+fn native_calls_are_not_variables() {
+    let (_, replies) = exchange(&[
+        open("var clock = 1;\nprint clock() + clock;\n"),
+        request(1, "textDocument/definition", at(1, 6)),
+        request(2, "textDocument/definition", at(1, 16))
+    ]);
+
+    assert_eq!(result(&replies, 1), &Value::Null);
+    assert_eq!(result(&replies, 2), &json!({ "uri": URI, "range": range((0, 4), (0, 9)) }));
+}
+
+#[test]
+fn malformed_messages_get_errors() {
+    let mut input = b"Content-Length: 5\r\n\r\n{oops".to_vec();
+    lsp::write_message(&mut input, &request(1, "shutdown", Value::Null)).unwrap();
+
+    let mut output = Vec::new();
+    Server::new(Dialect::Extended).run(&input[..], &mut output).unwrap();
+
+    let mut reader = &output[..];
+    let first: Value = serde_json::from_str(&lsp::read_message(&mut reader).unwrap().unwrap()).unwrap();
+    assert_eq!(first["id"], Value::Null);
+    assert_eq!(first["error"]["code"], -32700);
+
+    let second: Value = serde_json::from_str(&lsp::read_message(&mut reader).unwrap().unwrap()).unwrap();
+    assert_eq!(second["id"], 1);
+
+    for length in ["18446744073709551615", "1073741824"] {
+        let input = format!("Content-Length: {length}\r\n\r\n{{}}");
+        let err = Server::new(Dialect::Extended).run(input.as_bytes(), io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{length}");
+    }
+}
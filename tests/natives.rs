@@ -0,0 +1,140 @@
+//! Native functions work when their capability is granted, on both
+//! backends. Denied calls are covered by `tests/scripts/natives`.
+
+use std::{io::Write, process::{Command, Stdio}};
+
+use rust_tree_walk::{Backend, Capabilities, Capability, Dialect};
+
+mod common;
+
+fn run(code: &str, granted: &[Capability]) -> Vec<Result<String, String>> {
+    [Backend::TreeWalk, Backend::Bytecode].into_iter().map(|backend| {
+        let (output, result) = common::run_capturing(code, backend, Dialect::Extended, |executor| {
+            executor.set_capabilities(granted.iter().copied().collect());
+        });
+
+        result.map(|_| output).map_err(|err| err.to_string())
+    }).collect()
+}
+
+#[test]
+fn granted_natives_run() {
+    let cases = [
+        ("print clock() > 0;", Capability::Clock, "true\n".to_string()),
+        ("print env(\"CARGO_PKG_NAME\");", Capability::Env, format!("{}\n", env!("CARGO_PKG_NAME"))),
+        ("print env(\"RUST_TREE_WALK_UNSET\");", Capability::Env, "null\n".to_string()),
+        ("print readFile(\"tests/scripts/natives/exit_denied.lox\").startsWith(\"exit(3);\");", Capability::Files, "true\n".to_string())
+    ];
+
+    for (code, capability, expected) in cases {
+        for result in run(code, &[capability]) {
+            assert_eq!(result, Ok(expected.clone()), "{code}");
+        }
+
+        // Other capabilities do not help.
+        let others: Vec<Capability> = Capability::ALL.into_iter().filter(|other| *other != capability).collect();
+
+        for result in run(code, &others) {
+            assert!(result.unwrap_err().contains("Permission denied"), "{code}");
+        }
+    }
+}
+
+#[test]
+fn missing_files_are_runtime_errors() {
+    for result in run("readFile(\"tests/no such file\");", &[Capability::Files]) {
+        assert!(result.unwrap_err().starts_with("Error: Cannot read \"tests/no such file\""));
+    }
+}
+
+#[test]
+fn exit_stops_the_run() {
+    for backend in [Backend::TreeWalk, Backend::Bytecode] {
+        let (output, result) = common::run_capturing("print 1; exit(3); print 2;", backend, Dialect::Extended, |executor| {
+            executor.set_capabilities(Capabilities::all());
+        });
+
+        assert_eq!(result.unwrap_err().exit_code(), Some(3));
+        assert_eq!(output, "1\n");
+    }
+}
+
+fn seeded(seed: Option<u64>, backend: Backend) -> String {
+    let code = "print clock(); print clock(); print random(); print random(); print random() < 1;";
+    let (output, result) = common::run_capturing(code, backend, Dialect::Extended, |executor| {
+        executor.set_capabilities([Capability::Clock].into_iter().collect());
+        executor.set_deterministic(seed);
+    });
+
+    result.unwrap();
+    output
+}
+
+#[test]
+fn seeded_runs_are_reproducible() {
+    let expected = seeded(Some(42), Backend::TreeWalk);
+    assert!(expected.starts_with("0.001\n0.002\n"), "{expected}");
+    assert!(expected.ends_with("true\n"), "{expected}");
+
+    for backend in [Backend::TreeWalk, Backend::Bytecode] {
+        assert_eq!(seeded(Some(42), backend), expected, "{backend}");
+        assert_ne!(seeded(Some(43), backend), expected, "{backend}");
+
+        // Without a seed, random numbers differ from run to run.
+        assert_ne!(seeded(None, backend), seeded(None, backend), "{backend}");
+    }
+}
+
+/// Runs the command line interpreter on `code` with `stdin` as its input.
+fn cli(code: &str, arguments: &[&str], stdin: &str) -> (Option<i32>, String, String) {
+    let path = std::env::temp_dir().join(format!("natives-{}-{}.lox", std::process::id(), arguments.join("")));
+    std::fs::write(&path, code).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rust-tree-walk"))
+        .args(arguments)
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap()
+    )
+}
+
+#[test]
+fn command_line_grants_capabilities() {
+    let code = "print readLine(); print readLine(); print readLine(); exit(4);";
+
+    for backend in ["tree", "bytecode"] {
+        let (status, stdout, _) = cli(code, &["--backend", backend, "--allow", "stdin", "--allow", "exit"], "first\r\nsecond");
+        assert_eq!((status, stdout.as_str()), (Some(4), "first\nsecond\nnull\n"), "{backend}");
+
+        let (status, stdout, _) = cli(code, &["--backend", backend, "--allow-all"], "");
+        assert_eq!((status, stdout.as_str()), (Some(4), "null\nnull\nnull\n"), "{backend}");
+
+        let (status, stdout, stderr) = cli(code, &["--backend", backend, "--allow", "exit"], "first\n");
+        assert_ne!(status, Some(0), "{backend}");
+        assert_eq!(stdout, "", "{backend}");
+        assert!(stderr.contains("Permission denied"), "{backend}: {stderr}");
+    }
+}
+
+#[test]
+fn command_line_seed_is_reproducible() {
+    let code = "print random(); print clock();";
+    let arguments = ["--seed", "7", "--allow", "clock"];
+
+    let (status, first, _) = cli(code, &arguments, "");
+    assert_eq!(status, Some(0));
+    assert!(first.ends_with("\n0.001\n"), "{first}");
+    assert_eq!(cli(code, &arguments, "").1, first);
+}
@@ -0,0 +1,18 @@
+//! Syntax trees compare by structure, not by where the code was.
+
+use rust_tree_walk::{Dialect, Scanner, ast::Statement, parser::Parser};
+
+fn parse(code: &str) -> Vec<Statement> {
+    let scanner = Scanner::new(code, Dialect::Extended);
+    let mut tokens = scanner.tokens();
+    Parser::new(code, &mut tokens).parse().unwrap()
+}
+
+#[test]
+fn trees_compare_without_positions() {
+    let code = "print -a + \"x${b}\" * (c ? 1 : 2) or d.upper();";
+    let moved = format!("\n\n   {}", code.replace(' ', "  "));
+
+    assert_eq!(parse(code), parse(&moved));
+    assert_ne!(parse(code), parse("print -a - \"x${b}\" * (c ? 1 : 2) or d.upper();"));
+}
@@ -0,0 +1,61 @@
+//! Number literals: one rule for what may follow them in both dialects.
+
+use rust_tree_walk::{Dialect, Scanner, token::TokenType};
+
+fn scan(code: &str, dialect: Dialect) -> Result<Vec<(TokenType, &str)>, String> {
+    Scanner::new(code, dialect)
+        .tokens()
+        .map(|token| token.map(|token| (token.token_type(), token.lexeme(code))).map_err(|err| err.message()))
+        .collect()
+}
+
+#[test]
+fn numbers_scan_whole() {
+    for (code, dialect) in [
+        ("123", Dialect::Extended),
+        ("1.5", Dialect::Extended),
+        ("1.5", Dialect::Reference),
+        ("0xFF", Dialect::Extended),
+        ("0x_FF", Dialect::Extended),
+        ("0o17", Dialect::Extended),
+        ("0b1_0", Dialect::Extended),
+        ("1_000.000_1", Dialect::Extended),
+        ("2.5e-3", Dialect::Extended)
+    ] {
+        assert_eq!(scan(code, dialect), Ok(vec![(TokenType::Number, code)]), "{code} ({dialect})");
+    }
+}
+
+#[test]
+fn dot_needs_a_digit_after_it() {
+    use TokenType::*;
+
+    for dialect in [Dialect::Extended, Dialect::Reference] {
+        assert_eq!(scan("1.", dialect), Ok(vec![(Number, "1"), (Dot, ".")]));
+    }
+
+    assert_eq!(
+        scan("1.length()", Dialect::Extended),
+        Ok(vec![(Number, "1"), (Dot, "."), (Identifier, "length"), (LeftParen, "("), (RightParen, ")")])
+    );
+    assert_eq!(scan("1.5.upper", Dialect::Extended), Ok(vec![(Number, "1.5"), (Dot, "."), (Identifier, "upper")]));
+}
+
+#[test]
+fn names_cannot_follow_numbers() {
+    for (code, dialect, message) in [
+        ("123abc", Dialect::Extended, "Invalid digit \"a\" in number \"123\""),
+        ("123abc", Dialect::Reference, "Invalid digit \"a\" in number \"123\""),
+        ("1_000", Dialect::Reference, "Invalid digit \"_\" in number \"1\""),
+        ("0o19", Dialect::Extended, "Invalid digit \"9\" in number \"0o1\""),
+        ("0xFG", Dialect::Extended, "Invalid digit \"G\" in number \"0xF\""),
+        ("1.5x", Dialect::Extended, "Invalid digit \"x\" in number \"1.5\""),
+        ("1e5e", Dialect::Extended, "Invalid digit \"e\" in number \"1e5\""),
+        ("0x", Dialect::Extended, "Expect digits after \"0x\""),
+        ("0x_", Dialect::Extended, "Digit separator \"_\" must be between digits in \"0x_\""),
+        ("1__0", Dialect::Extended, "Digit separator \"_\" must be between digits in \"1_\""),
+        ("1e", Dialect::Extended, "Expect digits in exponent of \"1e\"")
+    ] {
+        assert_eq!(scan(code, dialect), Err(message.to_string()), "{code} ({dialect})");
+    }
+}
@@ -0,0 +1,106 @@
+//! Printing a parsed program and parsing the result again must give back the
+//! same syntax tree, and printing that must give back the same text.
+
+use rust_tree_walk::{
+    Backend,
+    Dialect,
+    Scanner,
+    ast::{Statement, Print, Expression, Literal},
+    heap::Heap,
+    value::Value,
+    optimizer::Optimizer,
+    parser::Parser,
+    unparser::Unparser
+};
+
+mod common;
+
+const PROGRAMS: u64 = 2000;
+
+fn parse(code: &str, dialect: Dialect) -> Option<Vec<Statement>> {
+    let scanner = Scanner::new(code, dialect);
+    let mut tokens = scanner.tokens();
+    Parser::with_dialect(code, &mut tokens, dialect).parse().ok()
+}
+
+fn check_round_trip(description: &str, code: &str, dialect: Dialect) {
+    let Some(statements) = parse(code, dialect) else {
+        return;
+    };
+
+    let printed = Unparser::new(dialect).unparse(&statements).unwrap();
+    let reparsed = parse(&printed, dialect)
+        .unwrap_or_else(|| panic!("{description}: printed program does not parse:\n{printed}\nfrom:\n{code}"));
+
+    assert_eq!(
+        statements,
+        reparsed,
+        "{description}: printed program parses differently:\n{printed}\nfrom:\n{code}"
+    );
+    assert_eq!(Unparser::new(dialect).unparse(&reparsed).unwrap(), printed, "{description}: printing is not stable");
+}
+
+#[test]
+fn generated_programs_round_trip() {
+    for (dialect, seed, code) in common::generated_programs(PROGRAMS) {
+        check_round_trip(&format!("seed {seed} ({dialect})"), &code, dialect);
+    }
+}
+
+#[test]
+fn scripts_round_trip() {
+    for script in common::scripts() {
+        check_round_trip(&script.name, &script.code, script.dialect);
+    }
+}
+
+#[test]
+fn infinite_literals_round_trip() {
+    check_round_trip("exponent", "print 1e999 + -1e999;", Dialect::Extended);
+
+    let digits = format!("print 1{};", "0".repeat(400));
+    check_round_trip("digits", &digits, Dialect::Reference);
+}
+
+fn output(code: &str) -> String {
+    let (output, result) = common::run_capturing(code, Backend::TreeWalk, Dialect::Extended, |_| {});
+    result.unwrap();
+
+    output
+}
+
+/// Folded strings can hold text that a string literal cannot, which the
+/// printed program has to build with an interpolation. Where only some
+/// parts fold, the printed program splits them differently but prints the
+/// same.
+#[test]
+fn folded_strings_round_trip() {
+    let cases = [
+        "print \"$\" + \"{1}\";",
+        "print \"a$\" + \"{\" + \"b$\" + \"{$\";",
+        "var x = 3;\nprint \"${\"$\" + \"{\"}${1 + 1}${x}\";"
+    ];
+    let optimize = |statements: &[Statement]| Optimizer::new(Dialect::Extended).optimize(statements);
+
+    for (index, code) in cases.into_iter().enumerate() {
+        let folded = optimize(&parse(code, Dialect::Extended).unwrap());
+
+        let printed = Unparser::new(Dialect::Extended).unparse(&folded).unwrap();
+        let reparsed = parse(&printed, Dialect::Extended).unwrap_or_else(|| panic!("{code}: printed {printed} does not parse"));
+
+        assert_eq!(output(&printed), output(code), "{code}: printed {printed}");
+
+        if index < 2 {
+            assert_eq!(optimize(&reparsed), folded, "{code}: printed {printed}");
+        }
+    }
+}
+
+#[test]
+fn list_literals_are_errors() {
+    let list = Heap::new().alloc_list(vec![Value::Number(1.0)]);
+    let statement = Statement::Print(Print::new(Expression::Literal(Literal::new(list, 1)), 1));
+
+    let err = Unparser::new(Dialect::Extended).unparse(&[statement]).unwrap_err();
+    assert_eq!(err.message(), "Cannot unparse a list value");
+}